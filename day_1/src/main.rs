@@ -1,67 +1,406 @@
+use std::fmt::Display;
 use std::fs::File;
 use std::io::{self, Read};
+use std::str::FromStr;
 
-fn extract_step(step: &str) -> i32 {
+#[derive(Debug, PartialEq)]
+enum StepErrorKind {
+    InvalidDirection(char),
+    // missing, not all digits, or too big for an i64
+    InvalidMagnitude(String),
+}
+
+#[derive(Debug, PartialEq)]
+struct StepError {
+    line: usize,
+    text: String,
+    kind: StepErrorKind,
+}
+
+impl Display for StepError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {} {:?}: ", self.line, self.text)?;
+        match &self.kind {
+            StepErrorKind::InvalidDirection(c) => write!(f, "expected L or R, found {:?}", c),
+            StepErrorKind::InvalidMagnitude(text) => {
+                write!(f, "expected a number of clicks, found {:?}", text)
+            }
+        }
+    }
+}
+
+// `L` or `R`, either case, then the number of clicks, with whitespace allowed around the whole
+// step. Left is negative.
+fn extract_step(step: &str) -> Result<i64, StepErrorKind> {
+    let step = step.trim();
     let mut characters = step.chars();
-    let direction_char = characters.next().expect("Must have an initial character!");
-    let sign: i32 = if direction_char == 'L' { -1 } else { 1 };
-    let number_string: String = characters.collect();
-    let magnitude: i32 = number_string
-        .parse()
-        .expect("Could not parse number_string");
-    return magnitude * sign;
+    let sign = match characters.next() {
+        Some('L' | 'l') => -1,
+        Some('R' | 'r') => 1,
+        Some(c) => return Err(StepErrorKind::InvalidDirection(c)),
+        None => return Err(StepErrorKind::InvalidMagnitude(String::new())),
+    };
+    let number_string = characters.as_str();
+    let invalid = || StepErrorKind::InvalidMagnitude(number_string.to_string());
+    // parse alone would let a sign through
+    if !number_string.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let magnitude: i64 = number_string.parse().map_err(|_| invalid())?;
+    Ok(magnitude * sign)
 }
 
-fn does_op_cross_zero(state: i32, op: i32) -> bool {
-    let remainder_op = op % 100;
-    // tricky special case, we need to check if we are already at boundary
-    if state == 0 {
-        return false; // Is impossible to loop back around by definition of remainder
+// The rotation on every line that has one, and an error for every other line that isn't blank
+fn parse_rotations(input: &str) -> (Vec<i64>, Vec<StepError>) {
+    let mut rotations = Vec::new();
+    let mut errors = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match extract_step(line) {
+            Ok(clicks) => rotations.push(clicks),
+            Err(kind) => errors.push(StepError {
+                line: i + 1,
+                text: line.to_string(),
+                kind,
+            }),
+        }
     }
-    // checks to see if state has moved more than 50 away from center; that is, beyond 0 or 100
-    if (state + remainder_op - 50).abs() >= 50 {
-        true
-    } else {
-        false
+    (rotations, errors)
+}
+
+// A dial numbered 0 to size - 1 that clicks round one number at a time, wrapping past either end
+#[derive(Clone, Debug)]
+struct Dial {
+    size: i64,
+    position: i64,
+}
+
+// What a single rotation did
+#[derive(Debug, PartialEq)]
+struct Rotation {
+    position: i64,
+    // clicks that left the dial pointing at 0, the last one included
+    zero_passes: i64,
+    ended_on_zero: bool,
+}
+
+impl Dial {
+    fn new(size: i64, position: i64) -> Dial {
+        assert!(size > 0, "A dial needs at least one position, got {}", size);
+        assert!(
+            (0..size).contains(&position),
+            "Start {} is not a position on a dial of size {}",
+            position,
+            size
+        );
+        Dial { size, position }
+    }
+
+    // Turns the dial by clicks, right for positive and left for negative
+    fn rotate(&mut self, clicks: i64) -> Rotation {
+        // turning left from p passes 0 as often as turning right from the mirror image of p does
+        let start = if clicks >= 0 {
+            self.position
+        } else {
+            (self.size - self.position) % self.size
+        };
+        // in i128 so that no number of clicks on any size of dial overflows, and the passes always
+        // fit back in an i64
+        let (size, clicks) = (i128::from(self.size), i128::from(clicks));
+        let zero_passes = ((i128::from(start) + clicks.abs()) / size) as i64;
+
+        self.position = (i128::from(self.position) + clicks).rem_euclid(size) as i64;
+        Rotation {
+            position: self.position,
+            zero_passes,
+            ended_on_zero: self.position == 0,
+        }
+    }
+
+    // Part 1: how many of the rotations leave the dial on 0
+    fn times_ended_on_zero(&self, rotations: &[i64]) -> usize {
+        let mut dial = self.clone();
+        rotations
+            .iter()
+            .filter(|&&clicks| dial.rotate(clicks).ended_on_zero)
+            .count()
+    }
+
+    // Part 2: how many clicks over all the rotations leave the dial on 0
+    fn times_passed_zero(&self, rotations: &[i64]) -> i128 {
+        let mut dial = self.clone();
+        rotations
+            .iter()
+            .map(|&clicks| i128::from(dial.rotate(clicks).zero_passes))
+            .sum()
     }
 }
 
+#[derive(Debug)]
+struct Options {
+    filename: String,
+    size: i64,
+    start: i64,
+    // leave out lines that aren't rotations and list them at the end instead of stopping
+    skip_invalid: bool,
+}
+
+fn number<T: FromStr>(name: &str, value: String) -> T {
+    value
+        .parse()
+        .unwrap_or_else(|_| panic!("{} needs a number, got {}", name, value))
+}
+
+fn options_from_args(mut args: impl Iterator<Item = String>) -> Options {
+    let mut options = Options {
+        filename: "input.txt".to_string(),
+        size: 100,
+        start: 50,
+        skip_invalid: false,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| panic!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--size" => options.size = number(&arg, value()),
+            "--start" => options.start = number(&arg, value()),
+            "--skip-invalid" => options.skip_invalid = true,
+            _ if arg.starts_with("--") => panic!(
+                "Unknown argument {}, expected an input file, --size N, --start N or \
+                 --skip-invalid",
+                arg
+            ),
+            _ => options.filename = arg,
+        }
+    }
+    options
+}
+
 fn main() -> io::Result<()> {
-    let mut file = File::open("input.txt")?;
+    let options = options_from_args(std::env::args().skip(1));
+    let mut file = File::open(&options.filename)?;
     let mut contents = String::new();
     file.read_to_string(&mut contents)?;
 
-    let lines = contents.lines();
+    let (rotations, errors) = parse_rotations(&contents);
+    if let Some(error) = errors.first()
+        && !options.skip_invalid
+    {
+        panic!("Invalid input: {}", error);
+    }
+    let dial = Dial::new(options.size, options.start);
 
-    let mut state = 50;
-    let mut zeros = 0;
+    println!("Part 1 Solution: {}", dial.times_ended_on_zero(&rotations));
+    println!("Part 2 Solution: {}", dial.times_passed_zero(&rotations));
 
-    for line in lines {
-        let scalar_operator = extract_step(line);
-        state = (state + scalar_operator) % 100;
-        if state == 0 {
-            zeros += 1
+    if !errors.is_empty() {
+        println!("Skipped {} invalid lines:", errors.len());
+        for error in &errors {
+            println!("  {}", error);
         }
     }
 
-    println!("Part 1 Solution: {}", zeros);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: [i64; 10] = [-68, -30, 48, -5, 60, -55, -1, -99, 14, -82];
+
+    // xorshift, the one source of random dials and rotations for the tests
+    struct Rng(u64);
 
-    let lines = contents.lines();
+    impl Rng {
+        fn new(seed: u64) -> Rng {
+            Rng(seed)
+        }
+
+        fn below(&mut self, bound: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % bound
+        }
 
-    let mut state = 50;
-    let mut zeros = 0;
+        // Up to 30 rotations of a dial starting at start, either way, weighted towards whole turns
+        // and towards landing exactly on 0
+        fn rotations(&mut self, size: i64, start: i64) -> Vec<i64> {
+            let mut position = start;
+            (0..self.below(30))
+                .map(|_| {
+                    let magnitude = match self.below(4) {
+                        // whole turns
+                        0 => size * self.below(4) as i64,
+                        // just far enough to land on 0, plus maybe some whole turns
+                        1 => size - position + size * self.below(3) as i64,
+                        _ => self.below(5 * size as u64) as i64,
+                    };
+                    let clicks = if self.below(2) == 0 {
+                        magnitude
+                    } else if self.below(4) == 0 {
+                        // the mirror of landing on 0 going right
+                        -(position + size * self.below(3) as i64)
+                    } else {
+                        -magnitude
+                    };
+                    position = (position + clicks).rem_euclid(size);
+                    clicks
+                })
+                .collect()
+        }
+    }
 
-    for line in lines {
-        let scalar_operator = extract_step(line);
-        let full_rotations = (scalar_operator / 100).abs();
-        zeros += full_rotations;
-        if does_op_cross_zero(state, scalar_operator) {
-            zeros += 1
+    // The slow way, one click at a time
+    fn rotate_by_clicks(dial: &mut Dial, clicks: i64) -> Rotation {
+        let mut zero_passes = 0;
+        for _ in 0..clicks.abs() {
+            dial.position = (dial.position + clicks.signum()).rem_euclid(dial.size);
+            if dial.position == 0 {
+                zero_passes += 1;
+            }
+        }
+        Rotation {
+            position: dial.position,
+            zero_passes,
+            ended_on_zero: dial.position == 0,
         }
-        state = ((state + scalar_operator) % 100 + 100) % 100;
     }
 
-    println!("Part 2 Solution: {}", zeros);
+    #[test]
+    fn test_rotate() {
+        let mut dial = Dial::new(100, 50);
+        assert_eq!(
+            dial.rotate(-68),
+            Rotation {
+                position: 82,
+                zero_passes: 1,
+                ended_on_zero: false
+            }
+        );
+        assert_eq!(dial.rotate(-30).zero_passes, 0);
+        assert!(dial.rotate(48).ended_on_zero);
+        // leaving 0 doesn't count, and each full turn passes it once more
+        assert_eq!(dial.rotate(-250).zero_passes, 2);
+        assert_eq!(dial.position, 50);
+        assert_eq!(dial.rotate(1000).zero_passes, 10);
+    }
 
-    Ok(())
+    #[test]
+    fn test_example() {
+        let dial = Dial::new(100, 50);
+        assert_eq!(dial.times_ended_on_zero(&EXAMPLE), 3);
+        assert_eq!(dial.times_passed_zero(&EXAMPLE), 6);
+
+        let small = Dial::new(5, 0);
+        assert_eq!(small.times_ended_on_zero(&[5, -10, 3]), 2);
+        assert_eq!(small.times_passed_zero(&[5, -10, 3]), 3);
+    }
+
+    #[test]
+    fn test_against_clicks() {
+        let mut rng = Rng::new(0x5DEECE66D);
+        for _ in 0..500 {
+            let size = 1 + rng.below(20) as i64;
+            let start = rng.below(size as u64) as i64;
+            let rotations = rng.rotations(size, start);
+
+            let mut fast = Dial::new(size, start);
+            let mut slow = Dial::new(size, start);
+            let mut ended_on_zero = 0;
+            let mut zero_passes = 0i128;
+            for &clicks in &rotations {
+                let expected = rotate_by_clicks(&mut slow, clicks);
+                assert_eq!(
+                    fast.rotate(clicks),
+                    expected,
+                    "size {} start {} rotations {:?}",
+                    size,
+                    start,
+                    rotations
+                );
+                ended_on_zero += expected.ended_on_zero as usize;
+                zero_passes += i128::from(expected.zero_passes);
+            }
+
+            let dial = Dial::new(size, start);
+            assert_eq!(dial.times_ended_on_zero(&rotations), ended_on_zero);
+            assert_eq!(dial.times_passed_zero(&rotations), zero_passes);
+        }
+    }
+
+    #[test]
+    fn test_extract_step() {
+        assert_eq!(extract_step("L68"), Ok(-68));
+        assert_eq!(extract_step("  r12\t"), Ok(12));
+        assert_eq!(extract_step("R0"), Ok(0));
+        assert_eq!(extract_step("R9223372036854775807"), Ok(i64::MAX));
+        assert_eq!(
+            extract_step("X5"),
+            Err(StepErrorKind::InvalidDirection('X'))
+        );
+        for magnitude in ["", "-5", "+5", "5 5", "9223372036854775808"] {
+            assert_eq!(
+                extract_step(&format!("L{}", magnitude)),
+                Err(StepErrorKind::InvalidMagnitude(magnitude.to_string()))
+            );
+        }
+
+        let (rotations, errors) = parse_rotations("L10\n\nup 3\n R5 \nL\n");
+        assert_eq!(rotations, vec![-10, 5]);
+        assert_eq!(
+            errors,
+            vec![
+                StepError {
+                    line: 3,
+                    text: "up 3".to_string(),
+                    kind: StepErrorKind::InvalidDirection('u')
+                },
+                StepError {
+                    line: 5,
+                    text: "L".to_string(),
+                    kind: StepErrorKind::InvalidMagnitude(String::new())
+                },
+            ]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "line 3 \"up 3\": expected L or R, found 'u'"
+        );
+    }
+
+    #[test]
+    fn test_wide_range() {
+        for (size, start) in [(100, 50), (1, 0), (7, 6), (i64::MAX, i64::MAX - 1)] {
+            for clicks in [i64::MAX, -i64::MAX, i64::MAX - 3, -(i64::MAX / 2)] {
+                let mut dial = Dial::new(size, start);
+                let rotation = dial.rotate(clicks);
+
+                let (size, start, clicks) = (size as i128, start as i128, clicks as i128);
+                let end = start + clicks;
+                let expected = if clicks >= 0 {
+                    end.div_euclid(size) - start.div_euclid(size)
+                } else {
+                    (start - 1).div_euclid(size) - (end - 1).div_euclid(size)
+                };
+                assert_eq!(rotation.position as i128, end.rem_euclid(size));
+                assert_eq!(
+                    rotation.zero_passes as i128, expected,
+                    "{} {}",
+                    size, clicks
+                );
+            }
+        }
+
+        let dial = Dial::new(1, 0);
+        assert_eq!(
+            dial.times_passed_zero(&[i64::MAX, -i64::MAX]),
+            2 * i64::MAX as i128
+        );
+    }
 }
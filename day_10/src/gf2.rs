@@ -0,0 +1,264 @@
+// Linear algebra over GF(2), where pressing a button twice is the same as not pressing it. Bits
+// are packed 64 to a word so the row operations are plain word XORs.
+
+use std::collections::{HashMap, VecDeque};
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BitVector {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitVector {
+    pub fn new(len: usize) -> BitVector {
+        BitVector {
+            words: vec![0; len.div_ceil(WORD_BITS)],
+            len,
+        }
+    }
+
+    pub fn from_indices(len: usize, indices: &[usize]) -> BitVector {
+        let mut vector = BitVector::new(len);
+        for &index in indices {
+            vector.flip(index);
+        }
+        vector
+    }
+
+    pub fn get(&self, index: usize) -> bool {
+        assert!(index < self.len, "bit {} out of range {}", index, self.len);
+        self.words[index / WORD_BITS] >> (index % WORD_BITS) & 1 == 1
+    }
+
+    pub fn flip(&mut self, index: usize) {
+        assert!(index < self.len, "bit {} out of range {}", index, self.len);
+        self.words[index / WORD_BITS] ^= 1 << (index % WORD_BITS);
+    }
+
+    pub fn xor_with(&mut self, other: &BitVector) {
+        for (word, other_word) in self.words.iter_mut().zip(&other.words) {
+            *word ^= other_word;
+        }
+    }
+
+    pub fn count_ones(&self) -> u32 {
+        self.words.iter().map(|word| word.count_ones()).sum()
+    }
+
+    pub fn ones(&self) -> Vec<usize> {
+        (0..self.len).filter(|&index| self.get(index)).collect()
+    }
+}
+
+// Returns the smallest set of buttons whose presses toggle exactly the lit lights, or None if no
+// combination does. The rows are lights and the unknowns are buttons.
+pub fn min_weight_solution(lights: &[bool], buttons: &[Vec<usize>]) -> Option<Vec<usize>> {
+    let num_buttons = buttons.len();
+
+    let mut rows: Vec<BitVector> = vec![BitVector::new(num_buttons); lights.len()];
+    for (button_index, button) in buttons.iter().enumerate() {
+        for &light in button {
+            rows[light].flip(button_index);
+        }
+    }
+    let mut targets = lights.to_vec();
+
+    // reduced row echelon form, pivots[i] is the button solved by row i
+    let mut pivots = Vec::new();
+    for col_index in 0..num_buttons {
+        let row_index = pivots.len();
+        let Some(pivot_row) = (row_index..rows.len()).find(|&i| rows[i].get(col_index)) else {
+            continue;
+        };
+        rows.swap(pivot_row, row_index);
+        targets.swap(pivot_row, row_index);
+
+        for other in 0..rows.len() {
+            if other != row_index && rows[other].get(col_index) {
+                let pivot = rows[row_index].clone();
+                rows[other].xor_with(&pivot);
+                targets[other] ^= targets[row_index];
+            }
+        }
+        pivots.push(col_index);
+    }
+
+    if targets[pivots.len()..].iter().any(|&target| target) {
+        return None;
+    }
+
+    let mut particular = BitVector::new(num_buttons);
+    for (row_index, &pivot_col) in pivots.iter().enumerate() {
+        if targets[row_index] {
+            particular.flip(pivot_col);
+        }
+    }
+
+    // one null space vector per free button: press it, then fix up the pivots it disturbs
+    let mut null_space = Vec::new();
+    for free_col in (0..num_buttons).filter(|col| !pivots.contains(col)) {
+        let mut vector = BitVector::new(num_buttons);
+        vector.flip(free_col);
+        for (row_index, &pivot_col) in pivots.iter().enumerate() {
+            if rows[row_index].get(free_col) {
+                vector.flip(pivot_col);
+            }
+        }
+        null_space.push(vector);
+    }
+
+    // with many more buttons than lights the span of the buttons is the smaller space to search, and
+    // past 63 free buttons the combinations can't be counted in a u64 anyway
+    if null_space.len() > pivots.len() || null_space.len() >= 64 {
+        return shortest_in_span(&targets_to_bits(lights), buttons, lights.len());
+    }
+
+    // walk every combination of the null space in Gray code order so each step is a single XOR
+    let mut current = particular.clone();
+    let mut best = particular;
+    for step in 1..(1u64 << null_space.len()) {
+        current.xor_with(&null_space[step.trailing_zeros() as usize]);
+        if current.count_ones() < best.count_ones() {
+            best = current.clone();
+        }
+    }
+
+    Some(best.ones())
+}
+
+fn targets_to_bits(lights: &[bool]) -> BitVector {
+    let lit: Vec<usize> = (0..lights.len()).filter(|&i| lights[i]).collect();
+    BitVector::from_indices(lights.len(), &lit)
+}
+
+// Breadth first search over light states, which only ever visits the 2^rank states the buttons can
+// reach. The first time the target is seen it has been reached with the fewest presses.
+fn shortest_in_span(
+    target: &BitVector,
+    buttons: &[Vec<usize>],
+    num_lights: usize,
+) -> Option<Vec<usize>> {
+    let button_bits: Vec<BitVector> = buttons
+        .iter()
+        .map(|button| BitVector::from_indices(num_lights, button))
+        .collect();
+
+    let start = BitVector::new(num_lights);
+    let mut came_from: HashMap<BitVector, Option<(BitVector, usize)>> = HashMap::new();
+    came_from.insert(start.clone(), None);
+
+    let mut to_visit = VecDeque::from([start]);
+    while let Some(state) = to_visit.pop_front() {
+        if &state == target {
+            let mut pressed = Vec::new();
+            let mut current = state;
+            while let Some((previous, button_index)) = came_from[&current].clone() {
+                pressed.push(button_index);
+                current = previous;
+            }
+            pressed.sort();
+            return Some(pressed);
+        }
+
+        for (button_index, button) in button_bits.iter().enumerate() {
+            let mut next = state.clone();
+            next.xor_with(button);
+            if !came_from.contains_key(&next) {
+                came_from.insert(next.clone(), Some((state.clone(), button_index)));
+                to_visit.push_back(next);
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rng;
+
+    #[test]
+    fn test_bit_vector() {
+        let mut vector = BitVector::from_indices(130, &[0, 64, 129]);
+        assert_eq!(vector.count_ones(), 3);
+        vector.xor_with(&BitVector::from_indices(130, &[64, 65]));
+        assert_eq!(vector.ones(), vec![0, 65, 129]);
+    }
+
+    #[test]
+    fn test_min_weight_solution() {
+        let buttons = vec![
+            vec![3],
+            vec![1, 3],
+            vec![2],
+            vec![2, 3],
+            vec![0, 2],
+            vec![0, 1],
+        ];
+        let solution = min_weight_solution(&[false, true, true, false], &buttons).unwrap();
+        assert_eq!(solution.len(), 2);
+
+        // the same button twice can never light a single light
+        let solution = min_weight_solution(&[true, false], &[vec![0, 1], vec![0, 1]]);
+        assert_eq!(solution, None);
+    }
+
+    #[test]
+    fn test_many_buttons() {
+        // 40 buttons over 8 lights, the answer is the single button equal to the target
+        let buttons: Vec<Vec<usize>> = (1..=40usize)
+            .map(|mask| (0..8).filter(|bit| (mask * 37) >> bit & 1 == 1).collect())
+            .collect();
+        let target_button = &buttons[17];
+        let lights: Vec<bool> = (0..8).map(|light| target_button.contains(&light)).collect();
+
+        let solution = min_weight_solution(&lights, &buttons).unwrap();
+        assert_eq!(solution.len(), 1);
+        assert_eq!(&buttons[solution[0]], target_button);
+    }
+
+    #[test]
+    fn test_wide_null_space() {
+        // 70 copies of each of two buttons leave 138 free buttons, one press of each still does it
+        let buttons: Vec<Vec<usize>> = (0..140).map(|i| vec![i % 2]).collect();
+        let solution = min_weight_solution(&[true, true], &buttons).unwrap();
+        assert_eq!(solution.len(), 2);
+        assert_ne!(solution[0] % 2, solution[1] % 2);
+    }
+
+    #[test]
+    fn test_random_lights() {
+        // against trying every subset of the buttons
+        let mut rng = Rng::new(0x9E3779B97F4A7C15);
+        for _ in 0..200 {
+            let num_lights = 1 + rng.below(6) as usize;
+            let buttons: Vec<Vec<usize>> = (0..1 + rng.below(8))
+                .map(|_| (0..num_lights).filter(|_| rng.below(2) == 1).collect())
+                .collect();
+            let lights: Vec<bool> = (0..num_lights).map(|_| rng.below(2) == 1).collect();
+
+            let fewest = (0..1u32 << buttons.len())
+                .filter(|mask| {
+                    let mut lit = vec![false; num_lights];
+                    for (i, button) in buttons.iter().enumerate() {
+                        if mask >> i & 1 == 1 {
+                            button.iter().for_each(|&light| lit[light] = !lit[light]);
+                        }
+                    }
+                    lit == lights
+                })
+                .map(|mask| mask.count_ones() as usize)
+                .min();
+            let solution = min_weight_solution(&lights, &buttons);
+            assert_eq!(
+                solution.map(|presses| presses.len()),
+                fewest,
+                "{:?}",
+                buttons
+            );
+        }
+    }
+}
@@ -1,11 +1,16 @@
-use itertools::Itertools;
-use std::cmp::{max, min};
-use std::collections::VecDeque;
-use std::fmt::{Display, write};
+mod gf2;
+mod matrix;
+mod rational;
+mod simplex;
+
+use std::fmt::Display;
 use std::fs::File;
 use std::io::Read;
-use std::ops::Range;
-use std::usize;
+use std::str::FromStr;
+
+use matrix::Matrix;
+use rational::Rational;
+use simplex::LinearProgram;
 
 fn read_input() -> String {
     let mut contents = String::new();
@@ -15,117 +20,255 @@ fn read_input() -> String {
     contents
 }
 
-fn get_delimiters_index(
-    input_line: &str,
-    (open_delimiter, close_delimiter): (char, char),
-) -> (usize, usize) {
-    let first_index = input_line.find(open_delimiter).expect(&format!(
-        "Did not find delimiter {} in {}",
-        open_delimiter, input_line
-    ));
-
-    let last_index = input_line.find(close_delimiter).expect(&format!(
-        "Did not find delimiter {} in {}",
-        close_delimiter, input_line
-    ));
-
-    (first_index, last_index)
+#[derive(Debug, PartialEq)]
+enum ParseErrorReason {
+    Expected(char),
+    UnexpectedCharacter(char),
+    UnexpectedEnd,
+    InvalidNumber(String),
+    NoLights,
+    NoButtons,
+    DuplicateIndex(usize),
+    IndexOutOfRange { index: usize, lights: usize },
+    JoltageCountMismatch { lights: usize, joltages: usize },
 }
 
-fn get_lights(input_line: &str) -> Vec<bool> {
-    let mut output = Vec::new();
+#[derive(Debug, PartialEq)]
+struct ParseError {
+    line: usize,
+    column: usize,
+    reason: ParseErrorReason,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match &self.reason {
+            ParseErrorReason::Expected(expected) => write!(f, "expected '{}'", expected),
+            ParseErrorReason::UnexpectedCharacter(found) => {
+                write!(f, "unexpected character '{}'", found)
+            }
+            ParseErrorReason::UnexpectedEnd => write!(f, "unexpected end of line"),
+            ParseErrorReason::InvalidNumber(number) => write!(f, "invalid number {}", number),
+            ParseErrorReason::NoLights => write!(f, "machine has no lights"),
+            ParseErrorReason::NoButtons => write!(f, "machine has no buttons"),
+            ParseErrorReason::DuplicateIndex(index) => {
+                write!(f, "button lists light {} twice", index)
+            }
+            ParseErrorReason::IndexOutOfRange { index, lights } => {
+                write!(f, "button index {} but only {} lights", index, lights)
+            }
+            ParseErrorReason::JoltageCountMismatch { lights, joltages } => {
+                write!(f, "{} joltages for {} lights", joltages, lights)
+            }
+        }
+    }
+}
+
+// Walks one `[lights] (buttons)... {joltages}` line a character at a time. Columns are 1-based.
+struct LineParser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl LineParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn error_at(&self, position: usize, reason: ParseErrorReason) -> ParseError {
+        ParseError {
+            line: 1,
+            column: position + 1,
+            reason,
+        }
+    }
+
+    fn unexpected(&self) -> ParseError {
+        match self.peek() {
+            Some(found) => {
+                self.error_at(self.position, ParseErrorReason::UnexpectedCharacter(found))
+            }
+            None => self.error_at(self.position, ParseErrorReason::UnexpectedEnd),
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(found) if found == expected => {
+                self.position += 1;
+                Ok(())
+            }
+            Some(_) => Err(self.error_at(self.position, ParseErrorReason::Expected(expected))),
+            None => Err(self.error_at(self.position, ParseErrorReason::UnexpectedEnd)),
+        }
+    }
+
+    // returns whether any whitespace was skipped
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.position;
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+        self.position > start
+    }
 
-    let (first_index, last_index) = get_delimiters_index(input_line, ('[', ']'));
+    fn lights(&mut self) -> Result<Vec<bool>, ParseError> {
+        let start = self.position;
+        self.expect('[')?;
+
+        let mut lights = Vec::new();
+        loop {
+            match self.peek() {
+                Some('.') => lights.push(false),
+                Some('#') => lights.push(true),
+                Some(']') => break,
+                _ => return Err(self.unexpected()),
+            }
+            self.position += 1;
+        }
+        self.position += 1;
 
-    for element in input_line[(first_index + 1)..last_index].chars() {
-        match element {
-            '.' => output.push(false),
-            '#' => output.push(true),
-            _ => panic!("exceeded bounds of bank {}", input_line),
+        if lights.is_empty() {
+            return Err(self.error_at(start, ParseErrorReason::NoLights));
         }
+        Ok(lights)
     }
 
-    output
+    // `open` value (`,` value)* `close`, returning each value with the position it started at
+    fn number_list(&mut self, open: char, close: char) -> Result<Vec<(usize, u32)>, ParseError> {
+        self.expect(open)?;
+
+        let mut values = Vec::new();
+        loop {
+            let start = self.position;
+            while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                self.position += 1;
+            }
+            if self.position == start {
+                return Err(self.unexpected());
+            }
+
+            let digits: String = self.chars[start..self.position].iter().collect();
+            let value = digits
+                .parse()
+                .map_err(|_| self.error_at(start, ParseErrorReason::InvalidNumber(digits)))?;
+            values.push((start, value));
+
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(c) if c == close => {
+                    self.position += 1;
+                    return Ok(values);
+                }
+                _ => return Err(self.unexpected()),
+            }
+        }
+    }
 }
 
-fn get_buttons(input_line: &str) -> Vec<Vec<usize>> {
-    let mut buttons = Vec::new();
+fn parse_problem(input_line: &str) -> Result<Problem, ParseError> {
+    let mut parser = LineParser {
+        chars: input_line.chars().collect(),
+        position: 0,
+    };
 
-    let mut input_iter = input_line.chars();
+    parser.skip_whitespace();
+    let lights = parser.lights()?;
 
-    loop {
-        let char = input_iter.next();
+    let mut buttons = Vec::new();
+    let joltages = loop {
+        if !parser.skip_whitespace() {
+            return Err(match parser.peek() {
+                Some(_) => parser.error_at(parser.position, ParseErrorReason::Expected(' ')),
+                None => parser.error_at(parser.position, ParseErrorReason::UnexpectedEnd),
+            });
+        }
 
-        match char {
+        match parser.peek() {
             Some('(') => {
-                let mut num = String::new();
-                let mut button: Vec<usize> = Vec::new();
-                loop {
-                    let char_option = input_iter.next();
-                    match char_option {
-                        Some(',') => {
-                            button.push(num.parse().unwrap());
-                            num = String::new();
-                        }
-                        Some(' ') => {}
-                        Some(')') => {
-                            button.push(num.parse().unwrap());
-                            break;
-                        }
-                        Some(digit) => {
-                            num.push(digit);
-                        }
-                        None => {
-                            panic!("No matching ')' found in string {}", input_line)
-                        }
+                let mut button = Vec::new();
+                for (position, index) in parser.number_list('(', ')')? {
+                    let index = index as usize;
+                    if index >= lights.len() {
+                        return Err(parser.error_at(
+                            position,
+                            ParseErrorReason::IndexOutOfRange {
+                                index,
+                                lights: lights.len(),
+                            },
+                        ));
+                    }
+                    if button.contains(&index) {
+                        return Err(
+                            parser.error_at(position, ParseErrorReason::DuplicateIndex(index))
+                        );
                     }
+                    button.push(index);
                 }
                 buttons.push(button);
             }
-            Some(_) => {}
-            None => {
-                break;
+            Some('{') => {
+                let start = parser.position;
+                if buttons.is_empty() {
+                    return Err(parser.error_at(start, ParseErrorReason::NoButtons));
+                }
+                let joltages = parser.number_list('{', '}')?;
+                if joltages.len() != lights.len() {
+                    return Err(parser.error_at(
+                        start,
+                        ParseErrorReason::JoltageCountMismatch {
+                            lights: lights.len(),
+                            joltages: joltages.len(),
+                        },
+                    ));
+                }
+                break joltages.into_iter().map(|(_, value)| value).collect();
             }
+            _ => return Err(parser.unexpected()),
         }
+    };
+
+    parser.skip_whitespace();
+    if parser.peek().is_some() {
+        return Err(parser.unexpected());
     }
 
-    buttons
+    Ok(Problem {
+        lights,
+        buttons,
+        batteries: joltages,
+    })
 }
 
-fn get_numeric(input_line: &str, delimiters: (char, char)) -> Vec<u32> {
-    let mut output = Vec::new();
-
-    let (first_index, last_index) = get_delimiters_index(input_line, delimiters);
+impl FromStr for Problem {
+    type Err = ParseError;
 
-    for element in input_line[(first_index + 1)..last_index].split(',') {
-        let value = element
-            .parse()
-            .expect(&format!("Element {} was evidently not a value", element));
-        output.push(value);
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_problem(s)
     }
-    output
 }
 
-fn parse(input: &str) -> Vec<Problem> {
+fn parse(input: &str) -> Result<Vec<Problem>, ParseError> {
     let mut output = Vec::new();
 
-    for line in input.lines() {
-        let lights = get_lights(line);
-        let buttons = get_buttons(line);
-        let batteries = get_numeric(line, ('{', '}'));
+    for (line_index, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
 
-        output.push(Problem {
-            lights: lights,
-            buttons: buttons,
-            batteries: batteries,
-        })
+        let problem = parse_problem(line).map_err(|error| ParseError {
+            line: line_index + 1,
+            ..error
+        })?;
+        output.push(problem);
     }
 
-    output
+    Ok(output)
 }
 
-fn xor_state(state: &Vec<bool>, button: &Vec<usize>) -> Vec<bool> {
-    let mut new_state = state.clone();
+fn xor_state(state: &[bool], button: &[usize]) -> Vec<bool> {
+    let mut new_state = state.to_vec();
 
     for index in button {
         new_state[*index] = !new_state[*index];
@@ -134,315 +277,403 @@ fn xor_state(state: &Vec<bool>, button: &Vec<usize>) -> Vec<bool> {
     new_state
 }
 
+#[derive(Debug, PartialEq)]
 struct Problem {
     lights: Vec<bool>,
     buttons: Vec<Vec<usize>>,
     batteries: Vec<u32>,
 }
 
-// We could do some complicated stuff here, or we could just brute force
-// Also, going to just allow the same button to be pressed twice in a row,
-// even though it will be slightly slower (but faster to write!)
-fn solve_problem_1(problem: &Problem) -> u64 {
+// Writes the machine back out in the input format, so parsing the output gives the same Problem
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lights: String = self
+            .lights
+            .iter()
+            .map(|&lit| if lit { '#' } else { '.' })
+            .collect();
+        write!(f, "[{}]", lights)?;
+
+        for button in &self.buttons {
+            let indices: Vec<String> = button.iter().map(|index| index.to_string()).collect();
+            write!(f, " ({})", indices.join(","))?;
+        }
+
+        let batteries: Vec<String> = self.batteries.iter().map(|x| x.to_string()).collect();
+        write!(f, " {{{}}}", batteries.join(","))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Mode {
+    Lights,
+    Joltage,
+}
+
+fn total_presses(witness: &[u64]) -> u64 {
+    witness.iter().sum()
+}
+
+// Replays a witness (presses per button) on a fresh machine and checks that it ends up exactly on
+// the target for the given mode
+fn verify(problem: &Problem, witness: &[u64], mode: Mode) -> bool {
+    if witness.len() != problem.buttons.len() {
+        return false;
+    }
+
+    match mode {
+        Mode::Lights => {
+            let mut state = vec![false; problem.lights.len()];
+            // pressing twice cancels out, so only the parity of the count matters
+            for (button, &presses) in problem.buttons.iter().zip(witness) {
+                if presses % 2 == 1 {
+                    state = xor_state(&state, button);
+                }
+            }
+            state == problem.lights
+        }
+        Mode::Joltage => {
+            let mut counters = vec![0u64; problem.batteries.len()];
+            for (button, &presses) in problem.buttons.iter().zip(witness) {
+                for &index in button {
+                    let Some(counter) = counters[index].checked_add(presses) else {
+                        return false;
+                    };
+                    counters[index] = counter;
+                }
+            }
+            counters
+                .iter()
+                .zip(&problem.batteries)
+                .all(|(&counter, &battery)| counter == battery as u64)
+        }
+    }
+}
+
+// Returns the presses per button (each at most once, since twice cancels out) that light the
+// lights with the fewest presses
+fn solve_problem_1(problem: &Problem) -> Vec<u64> {
     let Problem {
         lights,
         buttons,
         batteries: _,
     } = problem;
 
-    let mut index_iterator = (0..buttons.len()).powerset();
-
-    index_iterator.next();
+    let pressed = gf2::min_weight_solution(lights, buttons)
+        .unwrap_or_else(|| panic!("No buttons light up {:?}", lights));
 
-    let mut presses = 0;
-    for route in index_iterator {
-        let mut state = vec![false; lights.len()];
-        let route_len = route.len();
-        for button_index in route {
-            state = xor_state(&state, &buttons[button_index]);
-            if state.eq(lights) {
-                break;
-            };
-        }
-        if state.eq(lights) {
-            presses = route_len;
-            break;
-        };
+    let mut witness = vec![0; buttons.len()];
+    for index in pressed {
+        witness[index] = 1;
     }
 
-    presses as u64
+    debug_assert!(verify(problem, &witness, Mode::Lights));
+
+    witness
 }
 
-fn fast_route(buttons: Vec<Vec<usize>>, answer: Vec<u32>) -> u32 {
-    // if we assume that the minimum presses (the max num) is the solution, then we know that we
-    // can only use a subset of the buttons -> the ones that include the max
-    let max = answer.iter().max().unwrap();
+// The LP relaxation of part 2: counters[i] = sum of presses of the buttons touching i, where a
+// button can never be pressed more often than the smallest counter it feeds into
+fn joltage_program(buttons: &[Vec<usize>], batteries: &[u32]) -> LinearProgram {
+    let rows = (0..batteries.len())
+        .map(|counter| {
+            buttons
+                .iter()
+                .map(|button| {
+                    if button.contains(&counter) {
+                        Rational::ONE
+                    } else {
+                        Rational::ZERO
+                    }
+                })
+                .collect()
+        })
+        .collect();
 
-    let max_indexes: Vec<usize> = (0..answer.len()).filter(|i| &answer[*i] == max).collect();
+    let upper = buttons
+        .iter()
+        .map(|button| {
+            let bound = button
+                .iter()
+                .map(|&index| batteries[index])
+                .min()
+                .unwrap_or(0);
+            Rational::from(i128::from(bound))
+        })
+        .collect();
 
-    let mut sorted_index = (0..answer.len()).sorted_by(|i, j| Ord::cmp(&answer[*i], &answer[*j]));
+    LinearProgram::new(
+        Matrix::from_rows(rows),
+        batteries
+            .iter()
+            .map(|&x| Rational::from(i128::from(x)))
+            .collect(),
+        upper,
+    )
+}
 
-    let max_index = sorted_index.next().unwrap();
+// Returns the presses per button that reach the joltages with the fewest presses
+fn solve_problem_2(problem: &Problem) -> Vec<u64> {
+    let matrix: Matrix<Rational> = Matrix::new(problem.buttons.clone());
+    let solution = gaussian_elimination(&matrix, &problem.batteries);
 
-    let valid_buttons: Vec<Vec<usize>> = buttons
-        .clone()
-        .into_iter()
-        .filter(|button| button.contains(&max_index))
-        .collect();
+    let mut program = joltage_program(&problem.buttons, &problem.batteries);
+
+    let witness = solution
+        .min_presses(&mut program)
+        .unwrap_or_else(|| panic!("No valid presses for batteries {:?}", problem.batteries));
 
-    0
+    debug_assert!(verify(problem, &witness, Mode::Joltage));
+
+    witness
 }
 
 fn part_1(input: &str) -> u64 {
-    let problems = parse(&input);
+    let problems = parse(input).unwrap_or_else(|error| panic!("Invalid input: {}", error));
 
     let mut sum = 0;
     for problem in problems {
-        sum += solve_problem_1(&problem);
+        sum += total_presses(&solve_problem_1(&problem));
     }
 
     sum
 }
 
 fn part_2(input: &str) -> u64 {
-    let problems = parse(&input);
+    let problems = parse(input).unwrap_or_else(|error| panic!("Invalid input: {}", error));
 
     let mut sum = 0;
+    for problem in problems {
+        sum += total_presses(&solve_problem_2(&problem));
+    }
 
     sum
 }
+
 fn main() {
     let input = read_input();
-    // let part_1_answer = part_1(&input);
-    // println!("Part 1 Solution {}", part_1_answer);
+    let part_1_answer = part_1(&input);
+    println!("Part 1 Solution {}", part_1_answer);
 
     let part_2_answer = part_2(&input);
     println!("Part 2 Solution {}", part_2_answer);
 }
 
-#[derive(Clone)]
-struct Matrix {
-    values: Vec<i32>,
-    size: (usize, usize),
+// Every solution is const_values + sum(t_i * free_variables[i]), where t_i is the number of
+// presses of button free_indices[i]
+#[derive(Debug)]
+struct VectorEquation {
+    const_values: Vec<Rational>,
+    free_variables: Vec<Vec<Rational>>,
+    free_indices: Vec<usize>,
 }
 
-impl Matrix {
-    fn new(values_to_insert: Vec<Vec<usize>>) -> Matrix {
-        let num_rows = values_to_insert.iter().flatten().max().unwrap() + 1;
-        let num_cols = values_to_insert.len();
-
-        let mut values = vec![0; num_rows * num_cols];
+#[derive(Debug)]
+enum Solution {
+    Unique(Vec<Rational>),
+    Parametric(VectorEquation),
+    Inconsistent,
+}
 
-        for (col, value_rows) in values_to_insert.iter().enumerate() {
-            for &row in value_rows {
-                values[row * num_cols + col] = 1;
+impl Solution {
+    // returns the presses per button of a non-negative integer solution with the smallest total,
+    // using the LP relaxation of the same system to bound and prune the free variable search
+    fn min_presses(&self, program: &mut LinearProgram) -> Option<Vec<u64>> {
+        match self {
+            Solution::Unique(values) => presses_if_valid(values),
+            Solution::Parametric(equation) => {
+                if !program.tighten_upper_bounds() {
+                    return None;
+                }
+                let mut values = equation.const_values.clone();
+                let mut best = None;
+                equation.search_free_variables(0, &mut values, program, &mut best);
+                best
             }
+            Solution::Inconsistent => None,
         }
-
-        Matrix {
-            values,
-            size: (num_rows, num_cols),
-        }
-    }
-
-    fn get_row_range(self: &Self, row_index: usize) -> Range<usize> {
-        (row_index * self.size.1)..((row_index + 1) * self.size.1)
-    }
-
-    fn get_row(self: &Self, row_index: usize) -> &[i32] {
-        return &self.values[self.get_row_range(row_index)];
     }
+}
 
-    fn get_column(self: &Self, column_index: usize) -> Vec<i32> {
-        return self
-            .values
-            .iter()
-            .enumerate()
-            .filter(|(i, _)| (i % self.size.1) == column_index)
-            .map(|(_, &value)| value)
-            .collect();
-    }
+// A candidate only counts if every button is pressed a whole, non-negative number of times
+fn presses_if_valid(values: &[Rational]) -> Option<Vec<u64>> {
+    values
+        .iter()
+        .map(|value| match value.to_integer() {
+            Some(value) if value >= 0 => Some(value as u64),
+            _ => None,
+        })
+        .collect()
+}
 
-    fn swap_rows(&mut self, a: usize, b: usize) {
-        if a == b {
+impl VectorEquation {
+    // Branch and bound over the free variables. Fixing a free variable fixes its bounds in the LP,
+    // and a branch is dropped as soon as the relaxation can't beat the best witness so far.
+    fn search_free_variables(
+        &self,
+        depth: usize,
+        values: &mut Vec<Rational>,
+        program: &mut LinearProgram,
+        best: &mut Option<Vec<u64>>,
+    ) {
+        if depth == self.free_variables.len() {
+            if let Some(presses) = presses_if_valid(values)
+                && best
+                    .as_ref()
+                    .is_none_or(|best| total_presses(&presses) < total_presses(best))
+            {
+                *best = Some(presses);
+            }
             return;
         }
 
-        let row_size = self.size.1;
-
-        let (a, b) = if a < b { (a, b) } else { (b, a) };
-
-        let (left, right) = self.values.split_at_mut(b * row_size);
-
-        let row_a = &mut left[a * row_size..(a + 1) * row_size];
-        let row_b = &mut right[0..row_size];
-
-        row_a.swap_with_slice(row_b);
-    }
-
-    fn write_row(self: &mut Self, row_index: usize, row: &[i32]) {
-        let range = self.get_row_range(row_index);
-        self.values[range].copy_from_slice(row);
-    }
-
-    fn add_row_linear(
-        self: &mut Self,
-        coefficient: i32,
-        row_operator_index: usize,
-        row_recieve_index: usize,
-    ) {
-        let mut row_operator: Vec<i32> = self
-            .get_row(row_operator_index)
-            .iter()
-            .map(|x| x * coefficient)
-            .collect();
-
-        let row_recieve = self.get_row(row_recieve_index);
-
-        for i in 0..row_operator.len() {
-            row_operator[i] = row_operator[i] + row_recieve[i];
+        let ones = vec![Rational::ONE; program.upper.len()];
+        match program.minimize(&ones) {
+            None => return,
+            Some(relaxation) => {
+                if best
+                    .as_ref()
+                    .is_some_and(|best| relaxation.value.ceil() >= total_presses(best) as i128)
+                {
+                    return;
+                }
+            }
         }
 
-        self.write_row(row_recieve_index, row_operator.as_slice());
-    }
-}
-
-impl Display for Matrix {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut display = String::new();
-        for row_index in 0..self.size.0 {
-            for col_index in 0..self.size.1 {
-                display.push_str(&self.values[(row_index * self.size.1) + col_index].to_string());
+        let button = self.free_indices[depth];
+        let (lower, upper) = (program.lower[button], program.upper[button]);
+        let direction = &self.free_variables[depth];
+        let max_presses = upper.floor();
+        for presses in 0..=max_presses {
+            if presses > 0 {
+                values.iter_mut().zip(direction).for_each(|(x, &d)| *x += d);
             }
-            display.push('\n');
+            program.lower[button] = Rational::from(presses);
+            program.upper[button] = Rational::from(presses);
+            self.search_free_variables(depth + 1, values, program, best);
         }
-        f.write_str(&display)
+
+        // undo this level before handing back to the caller
+        program.lower[button] = lower;
+        program.upper[button] = upper;
+        let total = Rational::from(max_presses.max(0));
+        values
+            .iter_mut()
+            .zip(direction)
+            .for_each(|(x, &d)| *x -= d * total);
     }
 }
 
-#[derive(Debug)]
-struct VectorEquation {
-    const_values: Vec<i32>,
-    free_variables: Vec<Vec<i32>>,
-}
-// impl VectorEquation {
-//     // returns the minimum sum
-//     fn get_minimum(self: &Self) -> u32 {
-//         let mut values = Vec::new();
-//         for free_variable in self.free_variables {
-//             values.push(free_variable.iter().sum());
-//         }
-//
-//         // numbers that are negative are good to add, 0s are free, and positive numbers have cost.
-//         // So, we add the max we can of negative numbers, the least amount of positive numbers, and
-//         // any amount of 0s
-//
-//         for index in (0..values.len())
-//             .sorted_by(|i, j| Ord::cmp(&values[*i], &values[*j]))
-//             .into_iter()
-//         {
-//             let value = values[index];
-//             let free_variable = self.free_variables[index];
-//         }
-//
-//         0
-//     }
-// }
-
-fn gaussian_elimination(matrix: &Matrix, b: &mut Vec<i32>) -> VectorEquation {
+// Reduces [matrix | b] to reduced row echelon form with exact fractions, so any pivot can be
+// normalised. b may be longer than the matrix when no button touches the last counters.
+fn gaussian_elimination(matrix: &Matrix<Rational>, b: &[u32]) -> Solution {
+    assert!(
+        matrix.size.0 <= b.len(),
+        "Matrix has {} rows but only {} values were given",
+        matrix.size.0,
+        b.len()
+    );
+
     let mut row_index = 0;
     let mut col_index = 0;
-    let min_matrix_size = min(matrix.size.0, matrix.size.1);
     let mut matrix = matrix.clone();
+    let mut b: Vec<Rational> = b.iter().map(|&x| Rational::from(i128::from(x))).collect();
+    let mut pivots = Vec::new();
     while (row_index < matrix.size.0) & (col_index < matrix.size.1) {
-        // find a row that matches the specifications well (has a 1, zero values for rest
-        // (ideally))
-        let mut l1 = i32::MAX;
-        let mut row_operator_index = row_index;
-        for row_index in (row_index..matrix.size.0) {
-            let row = matrix.get_row(row_index);
-            if row[col_index] != 0 {
-                if row.iter().map(|x| x.abs()).sum::<i32>() < l1 {
-                    row_operator_index = row_index;
-                    l1 = row.iter().map(|x| x.abs()).sum::<i32>();
-                }
-            }
-        }
+        let row_operator_index =
+            (row_index..matrix.size.0).find(|&i| !matrix.get_row(i)[col_index].is_zero());
 
-        // normalize row (hopefully not necessary)
-        let row_operator = matrix.get_row(row_operator_index);
+        let Some(row_operator_index) = row_operator_index else {
+            // just iterate the col index, we didn't find a valid row for this one
+            col_index += 1;
+            continue;
+        };
 
-        if row_operator[col_index] != 1 {
-            if row_operator[col_index] == 0 {
-                // just iterate the col index, we didn't find a valid row for this one
-                col_index += 1;
-                continue;
-            } else if b[row_operator_index].abs() % row_operator[col_index].abs() != 0 {
-                panic!(
-                    "Agggh, the row is {:?} with row index {} and we are trying {} and we tried to normalize it but b is {:?} so the result is {}",
-                    row_operator,
-                    row_index,
-                    col_index,
-                    b,
-                    b[row_operator_index].abs() % row_operator[col_index].abs()
-                )
-            } else {
-                b[row_operator_index] = b[row_operator_index] / row_operator[col_index];
-
-                let new_row_operator: Vec<i32> = row_operator
-                    .iter()
-                    .map(|x| {
-                        assert!(x % row_operator[col_index] == 0);
-                        x / row_operator[col_index]
-                    })
-                    .collect();
-                matrix.write_row(row_operator_index, new_row_operator.as_slice());
-            }
-        }
+        // normalize row, the fractions make this always possible
+        let pivot = matrix.get_row(row_operator_index)[col_index];
+        let new_row_operator: Vec<Rational> = matrix
+            .get_row(row_operator_index)
+            .iter()
+            .map(|&x| x / pivot)
+            .collect();
+        matrix.write_row(row_operator_index, new_row_operator.as_slice());
+        b[row_operator_index] = b[row_operator_index] / pivot;
 
         // swap to position
-        //
         matrix.swap_rows(row_operator_index, row_index);
         b.swap(row_operator_index, row_index);
-        row_operator_index = row_index;
 
         for row_recieve_index in 0..matrix.size.0 {
-            if row_recieve_index == row_operator_index {
+            if row_recieve_index == row_index {
                 continue;
             }
             let coefficient = -matrix.get_row(row_recieve_index)[col_index];
-            matrix.add_row_linear(coefficient, row_operator_index, row_recieve_index);
-            b[row_recieve_index] = b[row_recieve_index] + coefficient * b[row_operator_index];
+            if coefficient.is_zero() {
+                continue;
+            }
+            matrix.add_row_linear(coefficient, row_index, row_recieve_index);
+            let b_operator = b[row_index];
+            b[row_recieve_index] += coefficient * b_operator;
         }
 
+        pivots.push(col_index);
         row_index += 1;
         col_index += 1;
     }
 
-    // identify free variables
-    let mut pivot_columns = Vec::new();
-    let mut free_columns = Vec::new();
+    // every row without a pivot reads 0 = b, including the counters no button touches
+    if b[pivots.len()..].iter().any(|x| !x.is_zero()) {
+        return Solution::Inconsistent;
+    }
 
-    for row_index in 0..matrix.size.0 {
-        let row = matrix.get_row(row_index);
+    // pivot row i solves for button pivots[i], everything else is free
+    let mut const_values = vec![Rational::ZERO; matrix.size.1];
+    for (pivot_row, &pivot_col) in pivots.iter().enumerate() {
+        const_values[pivot_col] = b[pivot_row];
+    }
 
-        let col_option = row.iter().position(|&x| x == 1);
-        if let Some(col_index) = col_option {
-            pivot_columns.push(col_index);
-        }
+    if pivots.len() == matrix.size.1 {
+        return Solution::Unique(const_values);
     }
 
-    for col_index in 0..min(matrix.size.0, matrix.size.1) {
-        if !pivot_columns.contains(&col_index) {
-            let mut column = matrix.get_column(col_index);
-            column[col_index] = 1;
-            free_columns.push(column);
+    let mut free_variables = Vec::new();
+    let mut free_indices = Vec::new();
+
+    for col_index in 0..matrix.size.1 {
+        if !pivots.contains(&col_index) {
+            let column = matrix.get_column(col_index);
+            let mut direction = vec![Rational::ZERO; matrix.size.1];
+            for (pivot_row, &pivot_col) in pivots.iter().enumerate() {
+                direction[pivot_col] = -column[pivot_row];
+            }
+            direction[col_index] = Rational::ONE;
+            free_variables.push(direction);
+            free_indices.push(col_index);
         }
     }
 
-    VectorEquation {
-        const_values: b.to_vec(),
-        free_variables: free_columns,
+    Solution::Parametric(VectorEquation {
+        const_values,
+        free_variables,
+        free_indices,
+    })
+}
+
+// xorshift64 for the tests of every module, so random machines are the same on every run
+#[cfg(test)]
+struct Rng(u64);
+
+#[cfg(test)]
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % bound
     }
 }
 
@@ -451,48 +682,242 @@ mod tests {
 
     use super::*;
 
-    // #[test]
-    // fn test_example_1() {
-    //     let example_1 = "[.##.] (3) (1,3) (2) (2,3) (0,2) (0,1) {3,5,4,7}";
-    //     let example_2 = "[...#.] (0,2,3,4) (2,3) (0,4) (0,1,2) (1,2,3,4) {7,5,12,7,2}";
-    //     let example_3 = "[.###.#] (0,1,2,3,4) (0,3,4) (0,1,2,4,5) (1,2) {10,11,11,5,10,5}";
-    //
-    //     let example_1_answer = part_1(example_1);
-    //     let example_2_answer = part_1(example_2);
-    //     let example_3_answer = part_1(example_3);
-    //
-    //     assert_eq!(example_1_answer, 2);
-    //     assert_eq!(example_2_answer, 3);
-    //     assert_eq!(example_3_answer, 2);
-    // }
-    //
-    // #[test]
-    // fn test_example_2() {
-    //     let example_1 = "[.##.] (3) (1,3) (2) (2,3) (0,2) (0,1) {3,5,4,7}";
-    //     let example_2 = "[...#.] (0,2,3,4) (2,3) (0,4) (0,1,2) (1,2,3,4) {7,5,12,7,2}";
-    //     let example_3 = "[.###.#] (0,1,2,3,4) (0,3,4) (0,1,2,4,5) (1,2) {10,11,11,5,10,5}";
-    //
-    //     let example_1_answer = part_2(example_1);
-    //     let example_2_answer = part_2(example_2);
-    //     let example_3_answer = part_2(example_3);
-    //
-    //     assert_eq!(example_1_answer, 10);
-    //     assert_eq!(example_2_answer, 12);
-    //     assert_eq!(example_3_answer, 11);
-    // }
-    //
-    // #[test]
-    // fn test_delimiters() {
-    //     let test_example = "(2,3) (4,3,2)";
-    //     let answer = vec![vec![2, 3], vec![4, 3, 2]];
-    //
-    //     assert_eq!(get_buttons(test_example), answer);
-    //
-    //     let test_example = "[..##..#]";
-    //     let answer = vec![false, false, true, true, false, false, true];
-    //
-    //     assert_eq!(get_lights(test_example), answer);
-    // }
+    #[test]
+    fn test_example_1() {
+        let example_1 = "[.##.] (3) (1,3) (2) (2,3) (0,2) (0,1) {3,5,4,7}";
+        let example_2 = "[...#.] (0,2,3,4) (2,3) (0,4) (0,1,2) (1,2,3,4) {7,5,12,7,2}";
+        let example_3 = "[.###.#] (0,1,2,3,4) (0,3,4) (0,1,2,4,5) (1,2) {10,11,11,5,10,5}";
+
+        let example_1_answer = part_1(example_1);
+        let example_2_answer = part_1(example_2);
+        let example_3_answer = part_1(example_3);
+
+        assert_eq!(example_1_answer, 2);
+        assert_eq!(example_2_answer, 3);
+        assert_eq!(example_3_answer, 2);
+    }
+
+    #[test]
+    fn test_example_2() {
+        let example_1 = "[.##.] (3) (1,3) (2) (2,3) (0,2) (0,1) {3,5,4,7}";
+        let example_2 = "[...#.] (0,2,3,4) (2,3) (0,4) (0,1,2) (1,2,3,4) {7,5,12,7,2}";
+        let example_3 = "[.###.#] (0,1,2,3,4) (0,3,4) (0,1,2,4,5) (1,2) {10,11,11,5,10,5}";
+
+        let example_1_answer = part_2(example_1);
+        let example_2_answer = part_2(example_2);
+        let example_3_answer = part_2(example_3);
+
+        assert_eq!(example_1_answer, 10);
+        assert_eq!(example_2_answer, 12);
+        assert_eq!(example_3_answer, 11);
+    }
+
+    #[test]
+    fn test_witnesses() {
+        let examples = "[.##.] (3) (1,3) (2) (2,3) (0,2) (0,1) {3,5,4,7}\n[...#.] (0,2,3,4) (2,3) (0,4) (0,1,2) (1,2,3,4) {7,5,12,7,2}\n[.###.#] (0,1,2,3,4) (0,3,4) (0,1,2,4,5) (1,2) {10,11,11,5,10,5}\n";
+
+        for (problem, expected) in parse(examples)
+            .unwrap()
+            .iter()
+            .zip([(2, 10), (3, 12), (2, 11)])
+        {
+            let lights_witness = solve_problem_1(problem);
+            assert!(verify(problem, &lights_witness, Mode::Lights));
+            assert_eq!(total_presses(&lights_witness), expected.0);
+
+            let joltage_witness = solve_problem_2(problem);
+            assert!(verify(problem, &joltage_witness, Mode::Joltage));
+            assert_eq!(total_presses(&joltage_witness), expected.1);
+        }
+    }
+
+    #[test]
+    fn test_verify_rejects() {
+        let problem: Problem = "[.##.] (3) (1,3) (2) (2,3) (0,2) (0,1) {3,5,4,7}"
+            .parse()
+            .unwrap();
+
+        // (1,3) then (2,3) lights .##. but pressing (1,3) a second time undoes it
+        assert!(verify(&problem, &[0, 1, 0, 1, 0, 0], Mode::Lights));
+        assert!(!verify(&problem, &[0, 2, 0, 1, 0, 0], Mode::Lights));
+        assert!(!verify(&problem, &[0, 1, 0, 1, 0, 0], Mode::Joltage));
+        assert!(!verify(&problem, &[0, 1, 0, 1, 0], Mode::Lights));
+        assert!(verify(&problem, &[1, 3, 0, 3, 1, 2], Mode::Joltage));
+
+        // huge counts from a broken solver are rejected rather than looped over or overflowed
+        assert!(verify(&problem, &[0, u64::MAX, 0, 1, 0, 0], Mode::Lights));
+        assert!(!verify(
+            &problem,
+            &[0, 0, 0, 0, u64::MAX, u64::MAX],
+            Mode::Joltage
+        ));
+    }
+
+    fn random_machine(rng: &mut Rng) -> Problem {
+        let num_lights = 1 + rng.below(4) as usize;
+        let num_buttons = 1 + rng.below(5) as usize;
+
+        let buttons: Vec<Vec<usize>> = (0..num_buttons)
+            .map(|_| {
+                let mut button: Vec<usize> =
+                    (0..num_lights).filter(|_| rng.below(2) == 1).collect();
+                if button.is_empty() {
+                    button.push(rng.below(num_lights as u64) as usize);
+                }
+                button
+            })
+            .collect();
+
+        let mut batteries = vec![0; num_lights];
+        for button in &buttons {
+            let presses = rng.below(4) as u32;
+            button.iter().for_each(|&index| batteries[index] += presses);
+        }
+
+        Problem {
+            lights: (0..num_lights).map(|_| rng.below(2) == 1).collect(),
+            buttons,
+            batteries,
+        }
+    }
+
+    // tries every press count for every button, up to the smallest battery it touches
+    fn brute_force_presses(problem: &Problem) -> Option<u64> {
+        let max_presses: Vec<u64> = problem
+            .buttons
+            .iter()
+            .map(|button| {
+                button
+                    .iter()
+                    .map(|&i| problem.batteries[i] as u64)
+                    .min()
+                    .unwrap()
+            })
+            .collect();
+        let mut witness = vec![0; problem.buttons.len()];
+        let mut best = None;
+
+        loop {
+            if verify(problem, &witness, Mode::Joltage) {
+                let total = total_presses(&witness);
+                if best.is_none_or(|best| total < best) {
+                    best = Some(total);
+                }
+            }
+
+            let Some(carry) = (0..witness.len()).find(|&i| witness[i] < max_presses[i]) else {
+                return best;
+            };
+            witness[carry] += 1;
+            witness[..carry].iter_mut().for_each(|presses| *presses = 0);
+        }
+    }
+
+    #[test]
+    fn test_random_machines() {
+        let mut rng = Rng::new(0x2545F4914F6CDD1D);
+
+        for _ in 0..300 {
+            let problem = random_machine(&mut rng);
+            let expected = brute_force_presses(&problem).unwrap();
+
+            let witness = solve_problem_2(&problem);
+            assert!(verify(&problem, &witness, Mode::Joltage), "{}", problem);
+            assert_eq!(total_presses(&witness), expected, "{}", problem);
+
+            // the relaxation can only ever be better than the integer answer
+            let mut program = joltage_program(&problem.buttons, &problem.batteries);
+            let ones = vec![Rational::ONE; problem.buttons.len()];
+            let relaxation = program.minimize(&ones).unwrap();
+            assert!(relaxation.value.ceil() <= expected as i128, "{}", problem);
+
+            assert!(program.tighten_upper_bounds());
+            for (upper, &presses) in program.upper.iter().zip(&witness) {
+                assert!(*upper >= Rational::from(i128::from(presses)), "{}", problem);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let problem: Problem = "[..##..#] (2,3) (4,3,2) {1,2,3,4,5,6,7}".parse().unwrap();
+
+        assert_eq!(problem.buttons, vec![vec![2, 3], vec![4, 3, 2]]);
+        assert_eq!(
+            problem.lights,
+            vec![false, false, true, true, false, false, true]
+        );
+        assert_eq!(problem.batteries, vec![1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let reason_at = |line: &str| {
+            let error = parse_problem(line).unwrap_err();
+            (error.column, error.reason)
+        };
+
+        assert_eq!(
+            reason_at("[.#x] (0) {1,2,3}"),
+            (4, ParseErrorReason::UnexpectedCharacter('x'))
+        );
+        assert_eq!(
+            reason_at("[.#.] (0,3) {1,2,3}"),
+            (
+                10,
+                ParseErrorReason::IndexOutOfRange {
+                    index: 3,
+                    lights: 3
+                }
+            )
+        );
+        assert_eq!(
+            reason_at("[.#.] (0,0) {1,2,3}"),
+            (10, ParseErrorReason::DuplicateIndex(0))
+        );
+        assert_eq!(
+            reason_at("[.#.] (0) {1,2}"),
+            (
+                11,
+                ParseErrorReason::JoltageCountMismatch {
+                    lights: 3,
+                    joltages: 2
+                }
+            )
+        );
+        assert_eq!(reason_at("[.#.] {1,2,3}"), (7, ParseErrorReason::NoButtons));
+        // a parenthesis after the joltages is not a button
+        assert_eq!(
+            reason_at("[.#.] (0) {1,2,3} (1)"),
+            (19, ParseErrorReason::UnexpectedCharacter('('))
+        );
+        assert_eq!(
+            reason_at("[.#.] (0) {1,2,3"),
+            (17, ParseErrorReason::UnexpectedEnd)
+        );
+        assert_eq!(
+            reason_at("[.#.](0) {1,2,3}"),
+            (6, ParseErrorReason::Expected(' '))
+        );
+        assert_eq!(reason_at("[] {}"), (1, ParseErrorReason::NoLights));
+
+        let error = parse("[#] (0) {1}\n[#] (1) {1}\n").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert_eq!(
+            error.to_string(),
+            "line 2, column 6: button index 1 but only 1 lights"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let example = "[.##.] (3) (1,3) (2) (2,3) (0,2) (0,1) {3,5,4,7}\n[...#.] (0,2,3,4) (2,3) (0,4) (0,1,2) (1,2,3,4) {7,5,12,7,2}\n";
+
+        for (problem, line) in parse(example).unwrap().iter().zip(example.lines()) {
+            assert_eq!(problem.to_string(), line);
+            assert_eq!(&problem.to_string().parse::<Problem>().unwrap(), problem);
+        }
+    }
 
     #[test]
     fn test_gaussian() {
@@ -500,34 +925,98 @@ mod tests {
         let example_2 = "[...#.] (0,2,3,4) (2,3) (0,4) (0,1,2) (1,2,3,4) {7,5,12,7,2}";
         let example_3 = "[.###.#] (0,1,2,3,4) (0,3,4) (0,1,2,4,5) (1,2) {10,11,11,5,10,5}";
 
-        let mut problems = parse(&example_1);
-        problems.append(&mut parse(&example_2));
-        problems.append(&mut parse(&example_3));
+        let mut problems = parse(example_1).unwrap();
+        problems.append(&mut parse(example_2).unwrap());
+        problems.append(&mut parse(example_3).unwrap());
 
         for problem in problems {
             let matrix = Matrix::new(problem.buttons);
-            let mut b = problem.batteries.iter().map(|x| *x as i32).collect();
+            let solution = gaussian_elimination(&matrix, &problem.batteries);
+            assert!(matches!(solution, Solution::Parametric(_)));
+        }
+    }
 
-            println!("{}", matrix);
-            println!("{:?}", b);
-            let solution = gaussian_elimination(&matrix, &mut b);
-            println!("{:?}", solution);
+    #[test]
+    fn test_gaussian_fractions() {
+        // x0 + x1 = 3, x0 = 3
+        let matrix = Matrix::new(vec![vec![0, 1], vec![0]]);
+        let solution = gaussian_elimination(&matrix, &[3, 3]);
+        match &solution {
+            Solution::Unique(values) => {
+                assert_eq!(values, &vec![Rational::from(3), Rational::ZERO]);
+            }
+            _ => panic!("Expected a unique solution, got {:?}", solution),
+        }
+
+        // each pair of buttons sums to 1, which the old integer pivoting panicked on
+        let matrix = Matrix::new(vec![vec![0, 1], vec![1, 2], vec![0, 2]]);
+        let solution = gaussian_elimination(&matrix, &[1, 1, 1]);
+        match &solution {
+            Solution::Unique(values) => {
+                assert_eq!(values, &vec![Rational::new(1, 2); 3]);
+            }
+            _ => panic!("Expected a unique solution, got {:?}", solution),
         }
+        let mut program = joltage_program(&[vec![0, 1], vec![1, 2], vec![0, 2]], &[1, 1, 1]);
+        assert_eq!(solution.min_presses(&mut program), None);
     }
+
     #[test]
+    fn test_large_joltages() {
+        // past i32::MAX, which used to wrap around to a negative target
+        let problem: Problem = "[.#] (0) (0,1) {4000000000,3000000000}".parse().unwrap();
+        let matrix = Matrix::new(problem.buttons.clone());
+        match gaussian_elimination(&matrix, &problem.batteries) {
+            Solution::Unique(values) => assert_eq!(
+                values,
+                vec![
+                    Rational::from(1_000_000_000i128),
+                    Rational::from(3_000_000_000i128)
+                ]
+            ),
+            solution => panic!("Expected a unique solution, got {:?}", solution),
+        }
+        assert_eq!(
+            solve_problem_2(&problem),
+            vec![1_000_000_000, 3_000_000_000]
+        );
+    }
+
+    #[test]
+    fn test_gaussian_inconsistent() {
+        // two copies of the same button can't hit different counts
+        let matrix = Matrix::new(vec![vec![0, 1]]);
+        let solution = gaussian_elimination(&matrix, &[2, 3]);
+        assert!(matches!(solution, Solution::Inconsistent));
+
+        // counter 2 isn't wired to any button
+        let matrix = Matrix::new(vec![vec![0], vec![1]]);
+        let solution = gaussian_elimination(&matrix, &[1, 1, 4]);
+        assert!(matches!(solution, Solution::Inconsistent));
+        let mut program = joltage_program(&[vec![0], vec![1]], &[1, 1, 4]);
+        assert_eq!(solution.min_presses(&mut program), None);
+    }
+
+    #[test]
+    #[ignore = "needs the puzzle input in input.txt"]
     fn test_input() {
+        assert!(
+            std::path::Path::new("input.txt").exists(),
+            "test_input needs the puzzle input in input.txt"
+        );
         let input = read_input();
 
-        let problems = parse(&input);
+        let problems = parse(&input).unwrap();
 
         for problem in problems {
-            let matrix = Matrix::new(problem.buttons);
-            let mut b = problem.batteries.iter().map(|x| *x as i32).collect();
-
-            println!("{}", matrix);
-            println!("{:?}", b);
-            let solution = gaussian_elimination(&matrix, &mut b);
-            println!("{:?}", solution);
+            let matrix = Matrix::new(problem.buttons.clone());
+            let solution = gaussian_elimination(&matrix, &problem.batteries);
+            assert!(
+                !matches!(solution, Solution::Inconsistent),
+                "No solution for {}\n{:?}",
+                matrix,
+                problem.batteries
+            );
         }
     }
 }
@@ -0,0 +1,626 @@
+// Dense row-major matrix over any exact scalar. Only part of the API is needed by the solvers, the
+// rest is here so the integer structure of a system (rank, determinant, normal forms) can be
+// inspected when an answer looks off.
+
+use std::fmt::{Debug, Display};
+use std::ops::{Add, Div, Mul, Neg, Range, Sub};
+
+pub trait Scalar:
+    Copy
+    + PartialEq
+    + Debug
+    + Display
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    fn zero() -> Self;
+    fn one() -> Self;
+
+    fn is_zero(&self) -> bool {
+        *self == Self::zero()
+    }
+}
+
+// Scalars with Euclidean division, which is what the normal forms need
+pub trait Integer: Scalar + Ord {
+    fn div_euclid(self, rhs: Self) -> Self;
+    fn rem_euclid(self, rhs: Self) -> Self;
+    fn abs(self) -> Self;
+}
+
+macro_rules! impl_integer {
+    ($($t:ty),*) => {
+        $(
+            impl Scalar for $t {
+                fn zero() -> Self {
+                    0
+                }
+
+                fn one() -> Self {
+                    1
+                }
+            }
+
+            impl Integer for $t {
+                fn div_euclid(self, rhs: Self) -> Self {
+                    <$t>::div_euclid(self, rhs)
+                }
+
+                fn rem_euclid(self, rhs: Self) -> Self {
+                    <$t>::rem_euclid(self, rhs)
+                }
+
+                fn abs(self) -> Self {
+                    <$t>::abs(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(i32, i64, i128);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix<T> {
+    values: Vec<T>,
+    pub size: (usize, usize),
+}
+
+// Ax = b over the integers has exactly the solutions particular + sum(k_i * kernel[i]) for integer
+// k_i
+#[derive(Debug, PartialEq)]
+pub struct IntegerSolution<T> {
+    pub particular: Vec<T>,
+    pub kernel: Vec<Vec<T>>,
+}
+
+impl<T: Scalar> Matrix<T> {
+    // Incidence matrix of the buttons: column j has a one in every row that button j touches
+    pub fn new(values_to_insert: Vec<Vec<usize>>) -> Matrix<T> {
+        let num_rows = values_to_insert.iter().flatten().max().unwrap() + 1;
+        let num_cols = values_to_insert.len();
+
+        let mut values = vec![T::zero(); num_rows * num_cols];
+
+        for (col, value_rows) in values_to_insert.iter().enumerate() {
+            for &row in value_rows {
+                values[row * num_cols + col] = T::one();
+            }
+        }
+
+        Matrix {
+            values,
+            size: (num_rows, num_cols),
+        }
+    }
+
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Matrix<T> {
+        let num_cols = rows.first().map_or(0, |row| row.len());
+        assert!(
+            rows.iter().all(|row| row.len() == num_cols),
+            "All rows must have {} columns",
+            num_cols
+        );
+
+        Matrix {
+            size: (rows.len(), num_cols),
+            values: rows.into_iter().flatten().collect(),
+        }
+    }
+
+    pub fn zeros(num_rows: usize, num_cols: usize) -> Matrix<T> {
+        Matrix {
+            values: vec![T::zero(); num_rows * num_cols],
+            size: (num_rows, num_cols),
+        }
+    }
+
+    pub fn identity(size: usize) -> Matrix<T> {
+        let mut matrix = Matrix::zeros(size, size);
+        for i in 0..size {
+            matrix.set(i, i, T::one());
+        }
+        matrix
+    }
+
+    pub fn get(&self, row_index: usize, col_index: usize) -> T {
+        self.values[row_index * self.size.1 + col_index]
+    }
+
+    pub fn set(&mut self, row_index: usize, col_index: usize, value: T) {
+        self.values[row_index * self.size.1 + col_index] = value;
+    }
+
+    fn get_row_range(&self, row_index: usize) -> Range<usize> {
+        (row_index * self.size.1)..((row_index + 1) * self.size.1)
+    }
+
+    pub fn get_row(&self, row_index: usize) -> &[T] {
+        &self.values[self.get_row_range(row_index)]
+    }
+
+    pub fn get_column(&self, column_index: usize) -> Vec<T> {
+        self.values
+            .iter()
+            .skip(column_index)
+            .step_by(self.size.1)
+            .copied()
+            .collect()
+    }
+
+    pub fn swap_rows(&mut self, a: usize, b: usize) {
+        if a == b {
+            return;
+        }
+
+        let row_size = self.size.1;
+
+        let (a, b) = if a < b { (a, b) } else { (b, a) };
+
+        let (left, right) = self.values.split_at_mut(b * row_size);
+
+        let row_a = &mut left[a * row_size..(a + 1) * row_size];
+        let row_b = &mut right[0..row_size];
+
+        row_a.swap_with_slice(row_b);
+    }
+
+    pub fn swap_columns(&mut self, a: usize, b: usize) {
+        for row_index in 0..self.size.0 {
+            let start = row_index * self.size.1;
+            self.values.swap(start + a, start + b);
+        }
+    }
+
+    pub fn write_row(&mut self, row_index: usize, row: &[T]) {
+        let range = self.get_row_range(row_index);
+        self.values[range].copy_from_slice(row);
+    }
+
+    pub fn add_row_linear(
+        &mut self,
+        coefficient: T,
+        row_operator_index: usize,
+        row_recieve_index: usize,
+    ) {
+        for col_index in 0..self.size.1 {
+            let value = self.get(row_recieve_index, col_index)
+                + coefficient * self.get(row_operator_index, col_index);
+            self.set(row_recieve_index, col_index, value);
+        }
+    }
+
+    pub fn add_column_linear(
+        &mut self,
+        coefficient: T,
+        col_operator_index: usize,
+        col_recieve_index: usize,
+    ) {
+        for row_index in 0..self.size.0 {
+            let value = self.get(row_index, col_recieve_index)
+                + coefficient * self.get(row_index, col_operator_index);
+            self.set(row_index, col_recieve_index, value);
+        }
+    }
+
+    fn negate_row(&mut self, row_index: usize) {
+        let range = self.get_row_range(row_index);
+        self.values[range].iter_mut().for_each(|x| *x = -*x);
+    }
+
+    #[allow(dead_code)]
+    pub fn transpose(&self) -> Matrix<T> {
+        let mut transposed = Matrix::zeros(self.size.1, self.size.0);
+        for row_index in 0..self.size.0 {
+            for col_index in 0..self.size.1 {
+                transposed.set(col_index, row_index, self.get(row_index, col_index));
+            }
+        }
+        transposed
+    }
+
+    pub fn mul_vector(&self, vector: &[T]) -> Vec<T> {
+        assert_eq!(
+            self.size.1,
+            vector.len(),
+            "Vector length must match columns"
+        );
+
+        (0..self.size.0)
+            .map(|row_index| {
+                self.get_row(row_index)
+                    .iter()
+                    .zip(vector)
+                    .fold(T::zero(), |sum, (&a, &b)| sum + a * b)
+            })
+            .collect()
+    }
+
+    #[allow(dead_code)]
+    pub fn mul_matrix(&self, other: &Matrix<T>) -> Matrix<T> {
+        assert_eq!(
+            self.size.1, other.size.0,
+            "Cannot multiply {:?} by {:?}",
+            self.size, other.size
+        );
+
+        let mut product = Matrix::zeros(self.size.0, other.size.1);
+        for row_index in 0..self.size.0 {
+            for col_index in 0..other.size.1 {
+                let value = (0..self.size.1).fold(T::zero(), |sum, k| {
+                    sum + self.get(row_index, k) * other.get(k, col_index)
+                });
+                product.set(row_index, col_index, value);
+            }
+        }
+        product
+    }
+
+    // Fraction free (Bareiss) elimination, every division is exact for integer entries. Returns the
+    // eliminated matrix, the pivot columns and the sign of the row permutation.
+    fn bareiss(&self) -> (Matrix<T>, Vec<usize>, bool) {
+        let mut matrix = self.clone();
+        let mut pivots = Vec::new();
+        let mut previous = T::one();
+        let mut negated = false;
+
+        for col_index in 0..self.size.1 {
+            let row_index = pivots.len();
+            if row_index == self.size.0 {
+                break;
+            }
+            let Some(pivot_row) =
+                (row_index..self.size.0).find(|&i| !matrix.get(i, col_index).is_zero())
+            else {
+                continue;
+            };
+            if pivot_row != row_index {
+                matrix.swap_rows(pivot_row, row_index);
+                negated = !negated;
+            }
+
+            let pivot = matrix.get(row_index, col_index);
+            for i in (row_index + 1)..self.size.0 {
+                let factor = matrix.get(i, col_index);
+                for j in col_index..self.size.1 {
+                    let value =
+                        (pivot * matrix.get(i, j) - factor * matrix.get(row_index, j)) / previous;
+                    matrix.set(i, j, value);
+                }
+            }
+            previous = pivot;
+            pivots.push(col_index);
+        }
+
+        (matrix, pivots, negated)
+    }
+
+    #[allow(dead_code)]
+    pub fn rank(&self) -> usize {
+        self.bareiss().1.len()
+    }
+
+    #[allow(dead_code)]
+    pub fn determinant(&self) -> T {
+        assert_eq!(
+            self.size.0, self.size.1,
+            "Determinant needs a square matrix"
+        );
+        if self.size.0 == 0 {
+            return T::one();
+        }
+
+        let (eliminated, pivots, negated) = self.bareiss();
+        if pivots.len() < self.size.0 {
+            return T::zero();
+        }
+
+        // with Bareiss the last pivot is the determinant of the permuted matrix
+        let last = self.size.0 - 1;
+        let determinant = eliminated.get(last, last);
+        if negated { -determinant } else { determinant }
+    }
+}
+
+// Nothing in the solvers needs these, they're for looking at the integer structure of a system by
+// hand
+#[allow(dead_code)]
+impl<T: Integer> Matrix<T> {
+    // Row-style Hermite normal form: returns (H, U) with U unimodular and U * self = H, where H is
+    // in row echelon form with positive pivots and the entries above each pivot reduced into
+    // [0, pivot)
+    pub fn hermite_normal_form(&self) -> (Matrix<T>, Matrix<T>) {
+        let mut hermite = self.clone();
+        let mut transform = Matrix::identity(self.size.0);
+
+        let mut row_index = 0;
+        for col_index in 0..self.size.1 {
+            if row_index == self.size.0 {
+                break;
+            }
+
+            // Euclid down the column until only the pivot row is left non-zero
+            loop {
+                let smallest = (row_index..self.size.0)
+                    .filter(|&i| !hermite.get(i, col_index).is_zero())
+                    .min_by_key(|&i| hermite.get(i, col_index).abs());
+                let Some(smallest) = smallest else {
+                    break;
+                };
+                hermite.swap_rows(smallest, row_index);
+                transform.swap_rows(smallest, row_index);
+
+                let pivot = hermite.get(row_index, col_index);
+                let mut cleared = true;
+                for i in (row_index + 1)..self.size.0 {
+                    let quotient = hermite.get(i, col_index) / pivot;
+                    if !quotient.is_zero() {
+                        hermite.add_row_linear(-quotient, row_index, i);
+                        transform.add_row_linear(-quotient, row_index, i);
+                    }
+                    cleared &= hermite.get(i, col_index).is_zero();
+                }
+                if cleared {
+                    break;
+                }
+            }
+
+            if hermite.get(row_index, col_index).is_zero() {
+                continue;
+            }
+            if hermite.get(row_index, col_index) < T::zero() {
+                hermite.negate_row(row_index);
+                transform.negate_row(row_index);
+            }
+
+            let pivot = hermite.get(row_index, col_index);
+            for i in 0..row_index {
+                let quotient = hermite.get(i, col_index).div_euclid(pivot);
+                if !quotient.is_zero() {
+                    hermite.add_row_linear(-quotient, row_index, i);
+                    transform.add_row_linear(-quotient, row_index, i);
+                }
+            }
+            row_index += 1;
+        }
+
+        (hermite, transform)
+    }
+
+    // Smith normal form: returns (D, U, V) with U and V unimodular and U * self * V = D, where D is
+    // diagonal with non-negative entries that each divide the next
+    pub fn smith_normal_form(&self) -> (Matrix<T>, Matrix<T>, Matrix<T>) {
+        let (num_rows, num_cols) = self.size;
+        let mut diagonal = self.clone();
+        let mut left = Matrix::identity(num_rows);
+        let mut right = Matrix::identity(num_cols);
+
+        for t in 0..num_rows.min(num_cols) {
+            loop {
+                // move the smallest remaining entry onto the diagonal
+                let smallest = (t..num_rows)
+                    .flat_map(|i| (t..num_cols).map(move |j| (i, j)))
+                    .filter(|&(i, j)| !diagonal.get(i, j).is_zero())
+                    .min_by_key(|&(i, j)| diagonal.get(i, j).abs());
+                let Some((pivot_row, pivot_col)) = smallest else {
+                    return (diagonal, left, right);
+                };
+                diagonal.swap_rows(pivot_row, t);
+                left.swap_rows(pivot_row, t);
+                diagonal.swap_columns(pivot_col, t);
+                right.swap_columns(pivot_col, t);
+
+                let pivot = diagonal.get(t, t);
+                let mut cleared = true;
+                for i in (t + 1)..num_rows {
+                    let quotient = diagonal.get(i, t) / pivot;
+                    diagonal.add_row_linear(-quotient, t, i);
+                    left.add_row_linear(-quotient, t, i);
+                    cleared &= diagonal.get(i, t).is_zero();
+                }
+                for j in (t + 1)..num_cols {
+                    let quotient = diagonal.get(t, j) / pivot;
+                    diagonal.add_column_linear(-quotient, t, j);
+                    right.add_column_linear(-quotient, t, j);
+                    cleared &= diagonal.get(t, j).is_zero();
+                }
+                if !cleared {
+                    continue;
+                }
+
+                // the pivot has to divide everything left, otherwise pull in an offending row and
+                // let the remainder become a smaller pivot
+                let offending = (t + 1..num_rows).find(|&i| {
+                    (t + 1..num_cols).any(|j| !diagonal.get(i, j).rem_euclid(pivot).is_zero())
+                });
+                match offending {
+                    Some(i) => {
+                        diagonal.add_row_linear(T::one(), i, t);
+                        left.add_row_linear(T::one(), i, t);
+                    }
+                    None => break,
+                }
+            }
+
+            if diagonal.get(t, t) < T::zero() {
+                diagonal.negate_row(t);
+                left.negate_row(t);
+            }
+        }
+
+        (diagonal, left, right)
+    }
+
+    // Decides whether self * x = b has an integer solution, and describes all of them if it does
+    pub fn integer_solution(&self, b: &[T]) -> Option<IntegerSolution<T>> {
+        assert_eq!(self.size.0, b.len(), "b must have one entry per row");
+
+        let (diagonal, left, right) = self.smith_normal_form();
+        let transformed_b = left.mul_vector(b);
+
+        // D y = U b, and x = V y
+        let mut y = vec![T::zero(); self.size.1];
+        let mut rank = 0;
+        for (i, &value) in transformed_b.iter().enumerate() {
+            let pivot = if i < self.size.1 {
+                diagonal.get(i, i)
+            } else {
+                T::zero()
+            };
+            if pivot.is_zero() {
+                if !value.is_zero() {
+                    return None;
+                }
+                continue;
+            }
+            if !value.rem_euclid(pivot).is_zero() {
+                return None;
+            }
+            y[i] = value.div_euclid(pivot);
+            rank += 1;
+        }
+
+        Some(IntegerSolution {
+            particular: right.mul_vector(&y),
+            kernel: (rank..self.size.1)
+                .map(|col_index| right.get_column(col_index))
+                .collect(),
+        })
+    }
+}
+
+impl<T: Display> Display for Matrix<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut display = String::new();
+        for row_index in 0..self.size.0 {
+            for col_index in 0..self.size.1 {
+                display.push_str(&self.values[(row_index * self.size.1) + col_index].to_string());
+            }
+            display.push('\n');
+        }
+        f.write_str(&display)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rational::Rational;
+
+    fn is_unimodular(matrix: &Matrix<i64>) -> bool {
+        matrix.determinant().abs() == 1
+    }
+
+    #[test]
+    fn test_get_column_and_transpose() {
+        let matrix = Matrix::from_rows(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+        assert_eq!(matrix.get_column(1), vec![2, 5]);
+        assert_eq!(
+            matrix.transpose(),
+            Matrix::from_rows(vec![vec![1, 4], vec![2, 5], vec![3, 6]])
+        );
+        assert_eq!(
+            Matrix::<i32>::new(vec![vec![0, 1], vec![1]]),
+            Matrix::from_rows(vec![vec![1, 0], vec![1, 1]])
+        );
+    }
+
+    #[test]
+    fn test_multiply() {
+        let a = Matrix::from_rows(vec![vec![1, 2], vec![3, 4]]);
+        let b = Matrix::from_rows(vec![vec![0, 1], vec![1, 0]]);
+        assert_eq!(
+            a.mul_matrix(&b),
+            Matrix::from_rows(vec![vec![2, 1], vec![4, 3]])
+        );
+        assert_eq!(a.mul_vector(&[1, -1]), vec![-1, -1]);
+        assert_eq!(a.mul_matrix(&Matrix::identity(2)), a);
+    }
+
+    #[test]
+    fn test_rank_and_determinant() {
+        let matrix = Matrix::from_rows(vec![vec![2, -1, 0], vec![-1, 2, -1], vec![0, -1, 2]]);
+        assert_eq!(matrix.determinant(), 4);
+        assert_eq!(matrix.rank(), 3);
+
+        let singular = Matrix::from_rows(vec![vec![1, 2, 3], vec![2, 4, 6], vec![0, 1, 1]]);
+        assert_eq!(singular.determinant(), 0);
+        assert_eq!(singular.rank(), 2);
+
+        let swapped = Matrix::from_rows(vec![vec![0, 1], vec![1, 0]]);
+        assert_eq!(swapped.determinant(), -1);
+
+        let fractions = Matrix::from_rows(vec![
+            vec![Rational::new(1, 2), Rational::ONE],
+            vec![Rational::ONE, Rational::from(4)],
+        ]);
+        assert_eq!(fractions.determinant(), Rational::ONE);
+    }
+
+    #[test]
+    fn test_hermite_normal_form() {
+        let matrix: Matrix<i64> = Matrix::from_rows(vec![
+            vec![3, 3, 1, 4],
+            vec![0, 1, 0, 0],
+            vec![0, 0, 19, 16],
+            vec![0, 0, 0, 3],
+        ]);
+        let (hermite, transform) = matrix.hermite_normal_form();
+
+        assert_eq!(transform.mul_matrix(&matrix), hermite);
+        assert!(is_unimodular(&transform));
+        assert_eq!(
+            hermite,
+            Matrix::from_rows(vec![
+                vec![3, 0, 1, 1],
+                vec![0, 1, 0, 0],
+                vec![0, 0, 19, 1],
+                vec![0, 0, 0, 3],
+            ])
+        );
+
+        let matrix: Matrix<i64> = Matrix::from_rows(vec![vec![2, 4], vec![3, 5], vec![1, 1]]);
+        let (hermite, transform) = matrix.hermite_normal_form();
+        assert_eq!(transform.mul_matrix(&matrix), hermite);
+        assert!(is_unimodular(&transform));
+        assert_eq!(
+            hermite,
+            Matrix::from_rows(vec![vec![1, 1], vec![0, 2], vec![0, 0]])
+        );
+    }
+
+    #[test]
+    fn test_smith_normal_form() {
+        let matrix: Matrix<i64> =
+            Matrix::from_rows(vec![vec![2, 4, 4], vec![-6, 6, 12], vec![10, -4, -16]]);
+        let (diagonal, left, right) = matrix.smith_normal_form();
+
+        assert_eq!(left.mul_matrix(&matrix).mul_matrix(&right), diagonal);
+        assert!(is_unimodular(&left));
+        assert!(is_unimodular(&right));
+        assert_eq!(
+            diagonal,
+            Matrix::from_rows(vec![vec![2, 0, 0], vec![0, 6, 0], vec![0, 0, 12]])
+        );
+    }
+
+    #[test]
+    fn test_integer_solution() {
+        // x + y = 3, x + y + 2z = 4 needs z = 1/2
+        let matrix: Matrix<i64> = Matrix::from_rows(vec![vec![1, 1, 0], vec![1, 1, 2]]);
+        assert_eq!(matrix.integer_solution(&[3, 4]), None);
+
+        let solution = matrix.integer_solution(&[3, 5]).unwrap();
+        assert_eq!(matrix.mul_vector(&solution.particular), vec![3, 5]);
+        assert_eq!(solution.kernel.len(), 1);
+        assert_eq!(matrix.mul_vector(&solution.kernel[0]), vec![0, 0]);
+
+        // rows beyond the rank must be consistent too
+        let matrix: Matrix<i64> = Matrix::from_rows(vec![vec![1, 1], vec![2, 2]]);
+        assert_eq!(matrix.integer_solution(&[1, 3]), None);
+        assert!(matrix.integer_solution(&[1, 2]).is_some());
+    }
+}
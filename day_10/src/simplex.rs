@@ -0,0 +1,259 @@
+// Bounded dual simplex over exact rationals for
+//
+//     minimise costs . x  subject to  constraints * x = rhs,  lower <= x <= upper
+//
+// Every variable is boxed, so starting each one at whichever bound its cost prefers is always dual
+// feasible and no phase one is needed. Each equality row gets a slack fixed to [0, 0] which forms
+// the starting basis. Ties are broken by the lowest index, which keeps the pivots deterministic
+// and rules out cycling.
+
+use crate::matrix::Matrix;
+use crate::rational::Rational;
+
+#[derive(Clone, Debug)]
+pub struct LinearProgram {
+    constraints: Matrix<Rational>,
+    rhs: Vec<Rational>,
+    pub lower: Vec<Rational>,
+    pub upper: Vec<Rational>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LpSolution {
+    pub value: Rational,
+    pub values: Vec<Rational>,
+}
+
+impl LinearProgram {
+    // Every variable starts bounded to [0, upper]
+    pub fn new(constraints: Matrix<Rational>, rhs: Vec<Rational>, upper: Vec<Rational>) -> Self {
+        assert_eq!(constraints.size.0, rhs.len(), "One rhs value per row");
+        assert_eq!(constraints.size.1, upper.len(), "One bound per variable");
+
+        LinearProgram {
+            lower: vec![Rational::ZERO; upper.len()],
+            constraints,
+            rhs,
+            upper,
+        }
+    }
+
+    // Returns None when no x satisfies the constraints and bounds
+    pub fn minimize(&self, costs: &[Rational]) -> Option<LpSolution> {
+        let (num_rows, num_structural) = self.constraints.size;
+        assert_eq!(costs.len(), num_structural, "One cost per variable");
+        if (0..num_structural).any(|j| self.lower[j] > self.upper[j]) {
+            return None;
+        }
+
+        let num_cols = num_structural + num_rows;
+
+        // tableau = B^-1 [A | I], which starts as [A | I] with the slacks basic
+        let mut tableau = Matrix::zeros(num_rows, num_cols);
+        for i in 0..num_rows {
+            for j in 0..num_structural {
+                tableau.set(i, j, self.constraints.get(i, j));
+            }
+            tableau.set(i, num_structural + i, Rational::ONE);
+        }
+        let mut basic_rhs = self.rhs.clone();
+        let mut basis: Vec<usize> = (num_structural..num_cols).collect();
+
+        let lower = |j: usize| {
+            if j < num_structural {
+                self.lower[j]
+            } else {
+                Rational::ZERO
+            }
+        };
+        let upper = |j: usize| {
+            if j < num_structural {
+                self.upper[j]
+            } else {
+                Rational::ZERO
+            }
+        };
+
+        let mut reduced_costs: Vec<Rational> = (0..num_cols)
+            .map(|j| costs.get(j).copied().unwrap_or(Rational::ZERO))
+            .collect();
+        let mut at_upper: Vec<bool> = reduced_costs.iter().map(|&d| d < Rational::ZERO).collect();
+
+        loop {
+            let mut is_basic = vec![false; num_cols];
+            basis.iter().for_each(|&j| is_basic[j] = true);
+
+            let nonbasic_value = |j: usize| if at_upper[j] { upper(j) } else { lower(j) };
+
+            // x_B = B^-1 b - B^-1 N x_N
+            let basic_values: Vec<Rational> = (0..num_rows)
+                .map(|i| {
+                    (0..num_cols)
+                        .filter(|&j| !is_basic[j])
+                        .fold(basic_rhs[i], |value, j| {
+                            value - tableau.get(i, j) * nonbasic_value(j)
+                        })
+                })
+                .collect();
+
+            // leave with the lowest numbered basic variable outside its bounds
+            let leaving = (0..num_rows)
+                .filter(|&i| {
+                    let value = basic_values[i];
+                    value < lower(basis[i]) || value > upper(basis[i])
+                })
+                .min_by_key(|&i| basis[i]);
+
+            let Some(row) = leaving else {
+                let mut values: Vec<Rational> = (0..num_cols).map(nonbasic_value).collect();
+                for (i, &j) in basis.iter().enumerate() {
+                    values[j] = basic_values[i];
+                }
+                values.truncate(num_structural);
+
+                let value = values
+                    .iter()
+                    .zip(costs)
+                    .fold(Rational::ZERO, |sum, (&x, &c)| sum + x * c);
+                return Some(LpSolution { value, values });
+            };
+
+            let increase = basic_values[row] < lower(basis[row]);
+
+            // ratio test over the nonbasic variables that can move the leaving one back in range
+            let mut entering: Option<(usize, Rational)> = None;
+            for j in 0..num_cols {
+                if is_basic[j] || lower(j) == upper(j) {
+                    continue;
+                }
+                let alpha = tableau.get(row, j);
+                if alpha.is_zero() {
+                    continue;
+                }
+                let helps = if increase {
+                    (alpha < Rational::ZERO) != at_upper[j]
+                } else {
+                    (alpha > Rational::ZERO) != at_upper[j]
+                };
+                if !helps {
+                    continue;
+                }
+
+                let mut ratio = reduced_costs[j] / alpha;
+                if ratio < Rational::ZERO {
+                    ratio = -ratio;
+                }
+                if entering.is_none_or(|(_, best)| ratio < best) {
+                    entering = Some((j, ratio));
+                }
+            }
+
+            let Some((entering, _)) = entering else {
+                // the violated row can't be repaired, so the bounds and constraints clash
+                return None;
+            };
+
+            let leaving_variable = basis[row];
+            at_upper[leaving_variable] = !increase;
+
+            let pivot = tableau.get(row, entering);
+            let normalised: Vec<Rational> =
+                tableau.get_row(row).iter().map(|&x| x / pivot).collect();
+            tableau.write_row(row, &normalised);
+            basic_rhs[row] = basic_rhs[row] / pivot;
+
+            for i in 0..num_rows {
+                let coefficient = tableau.get(i, entering);
+                if i == row || coefficient.is_zero() {
+                    continue;
+                }
+                tableau.add_row_linear(-coefficient, row, i);
+                let rhs_row = basic_rhs[row];
+                basic_rhs[i] -= coefficient * rhs_row;
+            }
+
+            let entering_cost = reduced_costs[entering];
+            for (j, cost) in reduced_costs.iter_mut().enumerate() {
+                *cost -= entering_cost * tableau.get(row, j);
+            }
+
+            basis[row] = entering;
+        }
+    }
+
+    // Tightens each upper bound to the largest value that variable takes in any LP solution.
+    // Returns false if the program is infeasible.
+    pub fn tighten_upper_bounds(&mut self) -> bool {
+        let num_variables = self.upper.len();
+        for j in 0..num_variables {
+            let mut costs = vec![Rational::ZERO; num_variables];
+            costs[j] = -Rational::ONE;
+            match self.minimize(&costs) {
+                Some(solution) => self.upper[j] = Rational::from(solution.values[j].floor()),
+                None => return false,
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rationals(values: &[i32]) -> Vec<Rational> {
+        values.iter().map(|&x| Rational::from(x)).collect()
+    }
+
+    #[test]
+    fn test_minimize() {
+        // x0 + x1 = 4, x1 + x2 = 3 with x <= 4, cheapest is to put everything through x1
+        let constraints = Matrix::from_rows(vec![rationals(&[1, 1, 0]), rationals(&[0, 1, 1])]);
+        let program = LinearProgram::new(constraints, rationals(&[4, 3]), rationals(&[4, 4, 4]));
+
+        let solution = program.minimize(&rationals(&[1, 1, 1])).unwrap();
+        assert_eq!(solution.value, Rational::from(4));
+        assert_eq!(solution.values, rationals(&[1, 3, 0]));
+
+        // maximising x2 instead
+        let solution = program.minimize(&rationals(&[0, 0, -1])).unwrap();
+        assert_eq!(solution.values, rationals(&[4, 0, 3]));
+    }
+
+    #[test]
+    fn test_fractional_optimum() {
+        // each pair sums to 1, only x = (1/2, 1/2, 1/2) works
+        let constraints = Matrix::from_rows(vec![
+            rationals(&[1, 1, 0]),
+            rationals(&[0, 1, 1]),
+            rationals(&[1, 0, 1]),
+        ]);
+        let program = LinearProgram::new(constraints, rationals(&[1, 1, 1]), rationals(&[1, 1, 1]));
+        let solution = program.minimize(&rationals(&[1, 1, 1])).unwrap();
+        assert_eq!(solution.value, Rational::new(3, 2));
+        assert_eq!(solution.values, vec![Rational::new(1, 2); 3]);
+    }
+
+    #[test]
+    fn test_infeasible() {
+        let constraints = Matrix::from_rows(vec![rationals(&[1, 1])]);
+        let mut program = LinearProgram::new(constraints, rationals(&[5]), rationals(&[2, 2]));
+        assert_eq!(program.minimize(&rationals(&[1, 1])), None);
+        assert!(!program.tighten_upper_bounds());
+
+        let constraints = Matrix::from_rows(vec![rationals(&[1, 1])]);
+        let mut program = LinearProgram::new(constraints, rationals(&[3]), rationals(&[5, 5]));
+        program.lower[0] = Rational::from(4);
+        assert_eq!(program.minimize(&rationals(&[1, 1])), None);
+    }
+
+    #[test]
+    fn test_tighten_upper_bounds() {
+        // x0 + x1 = 3, x1 + x2 = 1 so x1 and x2 can be at most 1
+        let constraints = Matrix::from_rows(vec![rationals(&[1, 1, 0]), rationals(&[0, 1, 1])]);
+        let mut program =
+            LinearProgram::new(constraints, rationals(&[3, 1]), rationals(&[10, 10, 10]));
+        assert!(program.tighten_upper_bounds());
+        assert_eq!(program.upper, rationals(&[3, 1, 1]));
+    }
+}
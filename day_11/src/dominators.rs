@@ -0,0 +1,269 @@
+// Dominators for the acyclic device graphs. Node a dominates b when every path from the root to b
+// passes through a. This is the iterative algorithm of Cooper, Harvey and Kennedy, which on a DAG
+// settles in a single pass over a topological order since every predecessor is done first.
+
+use crate::{GraphError, NodeGraph};
+
+#[derive(Debug)]
+pub struct DominatorTree {
+    root: usize,
+    // None for the root and for nodes the root can't reach
+    idom: Vec<Option<usize>>,
+}
+
+impl DominatorTree {
+    // order must start at root and list every predecessor of a node before the node
+    fn build(num_nodes: usize, order: &[usize], predecessors: &[Vec<usize>]) -> DominatorTree {
+        let root = order[0];
+        let mut position = vec![usize::MAX; num_nodes];
+        for (i, &node) in order.iter().enumerate() {
+            position[node] = i;
+        }
+
+        let mut idom: Vec<Option<usize>> = vec![None; num_nodes];
+        idom[root] = Some(root);
+
+        for &node in &order[1..] {
+            let mut reached = predecessors[node]
+                .iter()
+                .copied()
+                .filter(|&pred| idom[pred].is_some());
+            let Some(first) = reached.next() else {
+                continue;
+            };
+
+            // walk both fingers up the tree until they meet at the common dominator
+            let dominator = reached.fold(first, |mut a, mut b| {
+                while a != b {
+                    while position[a] > position[b] {
+                        a = idom[a].unwrap();
+                    }
+                    while position[b] > position[a] {
+                        b = idom[b].unwrap();
+                    }
+                }
+                a
+            });
+            idom[node] = Some(dominator);
+        }
+
+        idom[root] = None;
+        DominatorTree { root, idom }
+    }
+
+    pub fn immediate_dominator(&self, node: usize) -> Option<usize> {
+        self.idom[node]
+    }
+
+    // Every dominator of node, from node itself up to the root. Empty if the root can't reach it.
+    pub fn dominators(&self, node: usize) -> Vec<usize> {
+        if node != self.root && self.idom[node].is_none() {
+            return Vec::new();
+        }
+
+        let mut chain = vec![node];
+        let mut current = node;
+        while let Some(parent) = self.idom[current] {
+            chain.push(parent);
+            current = parent;
+        }
+        chain
+    }
+}
+
+impl NodeGraph {
+    // Dominators over everything reachable from start
+    pub fn dominator_tree(&self, start: &str) -> Result<DominatorTree, GraphError> {
+        let start = self.get_index(start)?;
+        let order = self.topological_order_from(start)?;
+
+        let mut predecessors = vec![Vec::new(); self.nodes.len()];
+        for &node in &order {
+            for &child in &self.get_node(node).connections_index {
+                predecessors[child].push(node);
+            }
+        }
+
+        Ok(DominatorTree::build(
+            self.nodes.len(),
+            &order,
+            &predecessors,
+        ))
+    }
+
+    // Post-dominators towards end over the nodes on some path from start to end, which are the
+    // dominators of the reversed graph rooted at end. Paths stop at end so its outputs are ignored.
+    pub fn post_dominator_tree(&self, start: &str, end: &str) -> Result<DominatorTree, GraphError> {
+        let through = self.paths_through(start, end)?;
+        let start = self.get_index(start)?;
+        let end = self.get_index(end)?;
+
+        let mut order: Vec<usize> = self
+            .topological_order_from(start)?
+            .into_iter()
+            .filter(|&node| through[node] > 0)
+            .collect();
+        order.reverse();
+        if order.is_empty() {
+            order.push(end);
+        }
+
+        // in the reversed graph the predecessors of a node are its outputs
+        let mut predecessors = vec![Vec::new(); self.nodes.len()];
+        for &node in order.iter().filter(|&&node| node != end) {
+            predecessors[node] = self
+                .get_node(node)
+                .connections_index
+                .iter()
+                .copied()
+                .filter(|&child| through[child] > 0)
+                .collect();
+        }
+
+        Ok(DominatorTree::build(
+            self.nodes.len(),
+            &order,
+            &predecessors,
+        ))
+    }
+
+    // Devices other than start and end that every path from start to end goes through, so removing
+    // any one of them disconnects the two. Ordered from start towards end.
+    pub fn bottlenecks(&self, start: &str, end: &str) -> Result<Vec<usize>, GraphError> {
+        let tree = self.dominator_tree(start)?;
+        let end = self.get_index(end)?;
+
+        let mut chain = tree.dominators(end);
+        chain.reverse();
+        Ok(chain
+            .into_iter()
+            .filter(|&node| node != tree.root && node != end)
+            .collect())
+    }
+
+    // The share of the paths from start to end that pass through each node
+    pub fn path_fractions(&self, start: &str, end: &str) -> Result<Vec<f64>, GraphError> {
+        let through = self.paths_through(start, end)?;
+        let total = through[self.get_index(start)?];
+        if total == 0 {
+            return Ok(vec![0.0; self.nodes.len()]);
+        }
+
+        Ok(through
+            .iter()
+            .map(|&paths| paths as f64 / total as f64)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParseOptions, Rng, parse_nodes};
+
+    fn graph(input: &str) -> NodeGraph {
+        parse_nodes(input, &ParseOptions::default()).unwrap()
+    }
+
+    fn ids(graph: &NodeGraph, nodes: &[usize]) -> Vec<String> {
+        nodes
+            .iter()
+            .map(|&node| graph.get_node(node).id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_dominators() {
+        // svr fans out and rejoins at fft, which fans out and rejoins at out
+        let graph = graph(
+            "svr: aaa bbb\naaa: fft\nbbb: fft ccc\nfft: ddd eee\nddd: out\neee: out\nccc: ddd\n",
+        );
+        let index = |id: &str| graph.get_index(id).unwrap();
+
+        let tree = graph.dominator_tree("svr").unwrap();
+        assert_eq!(tree.immediate_dominator(index("svr")), None);
+        assert_eq!(tree.immediate_dominator(index("fft")), Some(index("svr")));
+        assert_eq!(tree.immediate_dominator(index("ccc")), Some(index("bbb")));
+        assert_eq!(tree.immediate_dominator(index("eee")), Some(index("fft")));
+        assert_eq!(
+            ids(&graph, &tree.dominators(index("eee"))),
+            vec!["eee", "fft", "svr"]
+        );
+        assert!(tree.dominators(index("out")).contains(&index("svr")));
+        assert!(!tree.dominators(index("ddd")).contains(&index("fft")));
+
+        let post = graph.post_dominator_tree("svr", "out").unwrap();
+        assert_eq!(post.immediate_dominator(index("ccc")), Some(index("ddd")));
+        assert_eq!(post.immediate_dominator(index("aaa")), Some(index("fft")));
+        assert_eq!(post.immediate_dominator(index("bbb")), Some(index("out")));
+    }
+
+    #[test]
+    fn test_bottlenecks() {
+        let graph =
+            graph("you: aaa bbb\naaa: hub\nbbb: hub\nhub: ccc\nccc: ddd eee\nddd: out\neee: out\n");
+        let bottlenecks = graph.bottlenecks("you", "out").unwrap();
+        assert_eq!(ids(&graph, &bottlenecks), vec!["hub", "ccc"]);
+
+        let fractions = graph.path_fractions("you", "out").unwrap();
+        assert_eq!(fractions[graph.get_index("aaa").unwrap()], 0.5);
+        assert_eq!(fractions[graph.get_index("hub").unwrap()], 1.0);
+        assert_eq!(fractions[graph.get_index("ddd").unwrap()], 0.5);
+    }
+
+    #[test]
+    fn test_random_graphs() {
+        // a node dominates another exactly when removing it cuts every path to the other
+        let mut rng = Rng::new(0x2545F4914F6CDD1D);
+        for _ in 0..50 {
+            let size = 3 + rng.below(10) as usize;
+            let mut input = String::new();
+            for from in 0..size - 1 {
+                let outputs: Vec<String> = (from + 1..size)
+                    .filter(|_| rng.below(3) == 0)
+                    .map(|to| {
+                        if to == size - 1 {
+                            "out".to_string()
+                        } else {
+                            format!("n{}", to)
+                        }
+                    })
+                    .collect();
+                input.push_str(&format!("n{}: {}\n", from, outputs.join(" ")));
+            }
+            let graph = parse_nodes(&input, &ParseOptions::default()).unwrap();
+            let tree = graph.dominator_tree("n0").unwrap();
+            let through = graph.paths_through("n0", "out").unwrap();
+            let total = through[graph.get_index("n0").unwrap()];
+
+            let reaches = |removed: usize, target: usize| {
+                let mut seen = vec![false; graph.nodes.len()];
+                let mut stack = vec![0];
+                while let Some(node) = stack.pop() {
+                    if node == removed || seen[node] {
+                        continue;
+                    }
+                    seen[node] = true;
+                    stack.extend(&graph.get_node(node).connections_index);
+                }
+                seen[target]
+            };
+
+            for a in 1..graph.nodes.len() {
+                for b in 1..graph.nodes.len() {
+                    if a != b && reaches(usize::MAX, b) {
+                        assert_eq!(tree.dominators(b).contains(&a), !reaches(a, b), "{}", input);
+                    }
+                }
+            }
+
+            let end = graph.get_index("out").unwrap();
+            let expected: Vec<usize> = (1..graph.nodes.len())
+                .filter(|&node| node != end && total > 0 && through[node] == total)
+                .collect();
+            let mut bottlenecks = graph.bottlenecks("n0", "out").unwrap();
+            bottlenecks.sort();
+            assert_eq!(bottlenecks, expected, "{}", input);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+// Graphviz DOT and JSON renderings of a parsed device graph
+
+use std::fmt::Write;
+
+use crate::{GraphError, NodeGraph};
+
+// Ids can hold any character but whitespace and ':', so quote them for both formats
+fn escape(id: &str) -> String {
+    let mut escaped = String::with_capacity(id.len());
+    for c in id.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Each node is labelled with how many of the paths from start to end pass through it. Nodes in
+// highlighted are filled in, and nodes on no such path are greyed out along with their edges.
+pub fn to_dot(
+    graph: &NodeGraph,
+    start: &str,
+    end: &str,
+    highlighted: &[&str],
+) -> Result<String, GraphError> {
+    let through = graph.paths_through(start, end)?;
+
+    let mut is_highlighted = vec![false; graph.nodes.len()];
+    for id in highlighted {
+        is_highlighted[graph.get_index(id)?] = true;
+    }
+
+    let mut dot = String::from("digraph devices {\n    node [shape=box];\n");
+    for node in &graph.nodes {
+        let id = escape(&node.id);
+        let index = node.self_idx;
+        let mut attributes = if through[index] == 0 {
+            format!("label=\"{}\", color=grey, fontcolor=grey", id)
+        } else {
+            format!("label=\"{}\\n{}\"", id, through[index])
+        };
+        if is_highlighted[index] {
+            attributes.push_str(", style=filled, fillcolor=gold");
+        }
+        writeln!(dot, "    \"{}\" [{}];", id, attributes).unwrap();
+    }
+
+    for node in &graph.nodes {
+        for &child in &node.connections_index {
+            let colour = if through[node.self_idx] == 0 || through[child] == 0 {
+                " [color=grey]"
+            } else {
+                ""
+            };
+            writeln!(
+                dot,
+                "    \"{}\" -> \"{}\"{};",
+                escape(&node.id),
+                escape(&graph.get_node(child).id),
+                colour
+            )
+            .unwrap();
+        }
+    }
+    dot.push_str("}\n");
+
+    Ok(dot)
+}
+
+// An object from each id to the ids of its outputs, in the order the devices were parsed
+pub fn to_json(graph: &NodeGraph) -> String {
+    let mut json = String::from("{\n");
+    for (position, node) in graph.nodes.iter().enumerate() {
+        let outputs: Vec<String> = node
+            .connections_index
+            .iter()
+            .map(|&child| format!("\"{}\"", escape(&graph.get_node(child).id)))
+            .collect();
+        let separator = if position + 1 < graph.nodes.len() {
+            ","
+        } else {
+            ""
+        };
+        writeln!(
+            json,
+            "  \"{}\": [{}]{}",
+            escape(&node.id),
+            outputs.join(", "),
+            separator
+        )
+        .unwrap();
+    }
+    json.push_str("}\n");
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ParseOptions, parse_nodes};
+
+    #[test]
+    fn test_dot() {
+        let input =
+            "svr: aaa bbb\naaa: fft\nbbb: fft\nfft: out ccc\nccc: dac\ndac: out\nzzz: aaa\n";
+        let graph = parse_nodes(input, &ParseOptions::default()).unwrap();
+        let dot = to_dot(&graph, "svr", "out", &["fft", "dac"]).unwrap();
+
+        assert!(dot.starts_with("digraph devices {\n"));
+        assert!(dot.contains("\"svr\" [label=\"svr\\n4\"];"));
+        assert!(dot.contains("\"fft\" [label=\"fft\\n4\", style=filled, fillcolor=gold];"));
+        assert!(dot.contains("\"dac\" [label=\"dac\\n2\", style=filled, fillcolor=gold];"));
+        assert!(dot.contains("\"zzz\" [label=\"zzz\", color=grey, fontcolor=grey];"));
+        assert!(dot.contains("\"zzz\" -> \"aaa\" [color=grey];"));
+        assert!(dot.contains("\"fft\" -> \"out\";"));
+
+        assert_eq!(
+            to_dot(&graph, "svr", "out", &["nope"]),
+            Err(GraphError::UnknownNode("nope".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let input = "you: aaa bbb\naaa: out\nbbb: \"q\n\"q: out\n";
+        let graph = parse_nodes(input, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            to_json(&graph),
+            "{\n  \"you\": [\"aaa\", \"bbb\"],\n  \"aaa\": [\"out\"],\n  \"bbb\": [\"\\\"q\"],\n  \
+             \"\\\"q\": [\"out\"],\n  \"out\": []\n}\n"
+        );
+    }
+}
@@ -1,4 +1,9 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+mod dominators;
+mod export;
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt::Display;
 use std::fs::File;
 use std::io::Read;
 
@@ -10,159 +15,714 @@ fn read_input() -> String {
     contents
 }
 
-fn parse_nodes(input: &str) -> Vec<Node> {
-    let mut output = Vec::new();
+#[derive(Debug, PartialEq)]
+enum ParseErrorKind {
+    MissingColon,
+    EmptyId,
+    UndefinedNode(String),
+    DuplicateDefinition(String),
+}
 
-    let mut id_to_index = HashMap::new();
+#[derive(Debug, PartialEq)]
+struct ParseError {
+    line: usize,
+    kind: ParseErrorKind,
+}
 
-    for (i, line) in input.lines().enumerate() {
-        let (id, _) = line.split_once(':').unwrap();
-        id_to_index.insert(id, i);
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            ParseErrorKind::MissingColon => write!(f, "expected `id: outputs...`"),
+            ParseErrorKind::EmptyId => write!(f, "device has no id"),
+            ParseErrorKind::UndefinedNode(id) => write!(f, "output {} is never defined", id),
+            ParseErrorKind::DuplicateDefinition(id) => write!(f, "{} is defined twice", id),
+        }
     }
+}
 
-    id_to_index.insert("out", id_to_index.len());
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum UndefinedTargets {
+    // Only "out" may be used without a line of its own
+    #[default]
+    Reject,
+    // Anything without a line of its own is a device with no outputs
+    ImplicitSink,
+}
 
-    for (i, line) in input.lines().enumerate() {
-        let (id, nodes) = line.split_once(':').unwrap();
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum DuplicateDefinitions {
+    #[default]
+    Reject,
+    // Outputs from every line for the same id are combined, leaving out those an earlier line for
+    // the id already lists
+    Merge,
+}
 
-        let mut connections = Vec::new();
+#[derive(Clone, Copy, Debug, Default)]
+struct ParseOptions {
+    undefined_targets: UndefinedTargets,
+    duplicates: DuplicateDefinitions,
+}
 
-        for node in nodes.trim().split_whitespace() {
-            connections.push(
-                *id_to_index
-                    .get(node)
-                    .expect(&format!("String should have contained a node {}", node)),
-            );
+fn parse_nodes(input: &str, options: &ParseOptions) -> Result<NodeGraph, ParseError> {
+    let mut definitions: Vec<(usize, &str, Vec<&str>)> = Vec::new();
+    let mut id_to_index: HashMap<String, usize> = HashMap::new();
+
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line_number = i + 1;
+
+        let (id, outputs) = line.split_once(':').ok_or(ParseError {
+            line: line_number,
+            kind: ParseErrorKind::MissingColon,
+        })?;
+        let id = id.trim();
+        if id.is_empty() {
+            return Err(ParseError {
+                line: line_number,
+                kind: ParseErrorKind::EmptyId,
+            });
         }
+        let outputs: Vec<&str> = outputs.split_whitespace().collect();
+
+        match id_to_index.get(id) {
+            Some(&index) => match options.duplicates {
+                DuplicateDefinitions::Reject => {
+                    return Err(ParseError {
+                        line: line_number,
+                        kind: ParseErrorKind::DuplicateDefinition(id.to_string()),
+                    });
+                }
+                DuplicateDefinitions::Merge => {
+                    // an output an earlier line already has is the same edge, but repeats within
+                    // this line are parallel edges like anywhere else
+                    let earlier = definitions[index].2.clone();
+                    definitions[index].2.extend(
+                        outputs
+                            .into_iter()
+                            .filter(|output| !earlier.contains(output)),
+                    );
+                }
+            },
+            None => {
+                id_to_index.insert(id.to_string(), definitions.len());
+                definitions.push((line_number, id, outputs));
+            }
+        }
+    }
 
-        output.push(Node {
-            id: String::from(id),
+    let mut nodes: Vec<Node> = definitions
+        .iter()
+        .enumerate()
+        .map(|(i, (_, id, _))| Node {
+            id: id.to_string(),
             self_idx: i,
-            connections_index: connections,
+            connections_index: Vec::new(),
         })
+        .collect();
+
+    fn add_sink(
+        id: &str,
+        nodes: &mut Vec<Node>,
+        id_to_index: &mut HashMap<String, usize>,
+    ) -> usize {
+        let index = nodes.len();
+        id_to_index.insert(id.to_string(), index);
+        nodes.push(Node {
+            id: id.to_string(),
+            self_idx: index,
+            connections_index: Vec::new(),
+        });
+        index
     }
 
-    output.push(Node {
-        id: String::from("out"),
-        self_idx: output.len(),
-        connections_index: Vec::new(),
-    });
+    if !id_to_index.contains_key("out") {
+        add_sink("out", &mut nodes, &mut id_to_index);
+    }
 
-    output
+    for (i, (line_number, _, outputs)) in definitions.iter().enumerate() {
+        for &output in outputs {
+            let index = match id_to_index.get(output) {
+                Some(&index) => index,
+                None => match options.undefined_targets {
+                    UndefinedTargets::ImplicitSink => {
+                        add_sink(output, &mut nodes, &mut id_to_index)
+                    }
+                    UndefinedTargets::Reject => {
+                        return Err(ParseError {
+                            line: *line_number,
+                            kind: ParseErrorKind::UndefinedNode(output.to_string()),
+                        });
+                    }
+                },
+            };
+            // a repeated output is a parallel edge and another way through
+            nodes[i].connections_index.push(index);
+        }
+    }
+
+    Ok(NodeGraph { nodes, id_to_index })
 }
 
-#[derive(Clone, PartialEq)]
+// Each required node doubles the size of the count_paths table
+const MAX_REQUIRED: usize = 16;
+
+#[derive(Clone, Debug, PartialEq)]
 struct Node {
     id: String,
     self_idx: usize,
     connections_index: Vec<usize>,
 }
 
-impl Node {
-    fn is_out(self: &Self) -> bool {
-        self.id == "out"
-    }
-}
-
+#[derive(Debug)]
 struct NodeGraph {
     nodes: Vec<Node>,
+    id_to_index: HashMap<String, usize>,
+}
+
+#[derive(Debug, PartialEq)]
+enum GraphError {
+    // The ids around the cycle, in edge order
+    Cycle(Vec<String>),
+    UnknownNode(String),
+    // The device whose path count no longer fits in a u64
+    Overflow(String),
+}
+
+impl Display for GraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphError::Cycle(ids) => write!(f, "cycle through {} -> {}", ids.join(" -> "), ids[0]),
+            GraphError::UnknownNode(id) => write!(f, "no device called {}", id),
+            GraphError::Overflow(id) => write!(f, "more than {} paths from {}", u64::MAX, id),
+        }
+    }
 }
 
 impl NodeGraph {
-    fn get_node(self: &Self, index: usize) -> &Node {
+    fn get_node(&self, index: usize) -> &Node {
         &self.nodes[index]
     }
 
-    fn get_node_from_id(self: &Self, id: &str) -> &Node {
-        self.nodes.iter().find(|node| node.id == id).unwrap()
+    fn get_node_from_id(&self, id: &str) -> Option<&Node> {
+        self.id_to_index.get(id).map(|&index| self.get_node(index))
     }
-}
 
-fn part_1(input: &str) -> u32 {
-    let nodes = parse_nodes(input);
-    let graph = NodeGraph { nodes: nodes };
+    fn get_index(&self, id: &str) -> Result<usize, GraphError> {
+        self.get_node_from_id(id)
+            .map(|node| node.self_idx)
+            .ok_or_else(|| GraphError::UnknownNode(id.to_string()))
+    }
+
+    // Topological order of the nodes reachable from start, using an iterative DFS. A node finishes
+    // after all of its children, so the reversed finishing order is topological. Meeting a node
+    // that is still on the stack closes a cycle.
+    fn topological_order_from(&self, start: usize) -> Result<Vec<usize>, GraphError> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            Unvisited,
+            OnStack,
+            Finished,
+        }
+
+        let mut state = vec![State::Unvisited; self.nodes.len()];
+        let mut finished = Vec::new();
+
+        // (node, index of the next connection to follow)
+        let mut stack = vec![(start, 0)];
+        state[start] = State::OnStack;
+
+        while let Some(&mut (node, ref mut next)) = stack.last_mut() {
+            let connections = &self.get_node(node).connections_index;
+            if *next == connections.len() {
+                state[node] = State::Finished;
+                finished.push(node);
+                stack.pop();
+                continue;
+            }
+
+            let child = connections[*next];
+            *next += 1;
+            match state[child] {
+                State::Unvisited => {
+                    state[child] = State::OnStack;
+                    stack.push((child, 0));
+                }
+                State::OnStack => {
+                    let cycle_start = stack.iter().position(|&(n, _)| n == child).unwrap();
+                    return Err(GraphError::Cycle(
+                        stack[cycle_start..]
+                            .iter()
+                            .map(|&(n, _)| self.get_node(n).id.clone())
+                            .collect(),
+                    ));
+                }
+                State::Finished => {}
+            }
+        }
+
+        finished.reverse();
+        Ok(finished)
+    }
+
+    fn overflow(&self, node: usize) -> GraphError {
+        GraphError::Overflow(self.get_node(node).id.clone())
+    }
+
+    // Paths from every node in order to end in O(V + E). Paths stop as soon as they reach end.
+    fn count_paths_in_order(&self, order: &[usize], end: usize) -> Result<Vec<u64>, GraphError> {
+        let mut counts = vec![0u64; self.nodes.len()];
+
+        for &node in order.iter().rev() {
+            counts[node] = if node == end {
+                1
+            } else {
+                self.get_node(node)
+                    .connections_index
+                    .iter()
+                    .try_fold(0u64, |sum, &child| sum.checked_add(counts[child]))
+                    .ok_or_else(|| self.overflow(node))?
+            };
+        }
+
+        Ok(counts)
+    }
 
-    let start_node = graph.get_node_from_id("you");
+    // Paths from start to every node in order, where order starts at start. Paths stop as soon as
+    // they reach end.
+    fn count_paths_from(
+        &self,
+        order: &[usize],
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<u64>, GraphError> {
+        let mut counts = vec![0u64; self.nodes.len()];
+        counts[start] = 1;
+
+        for &node in order {
+            if node == end {
+                continue;
+            }
+            for &child in &self.get_node(node).connections_index {
+                counts[child] = counts[child]
+                    .checked_add(counts[node])
+                    .ok_or_else(|| self.overflow(child))?;
+            }
+        }
 
-    let mut to_visit = VecDeque::new();
+        Ok(counts)
+    }
 
-    to_visit.push_back(start_node);
+    // How many of the paths from start to end pass through each node. Zero for nodes that start
+    // can't reach or that can't reach end.
+    fn paths_through(&self, start: &str, end: &str) -> Result<Vec<u128>, GraphError> {
+        let start = self.get_index(start)?;
+        let end = self.get_index(end)?;
+        let order = self.topological_order_from(start)?;
+
+        let from_start = self.count_paths_from(&order, start, end)?;
+        let to_end = self.count_paths_in_order(&order, end)?;
+
+        Ok(from_start
+            .iter()
+            .zip(&to_end)
+            .map(|(&before, &after)| before as u128 * after as u128)
+            .collect())
+    }
 
-    let mut paths = 0;
-    loop {
-        let current_node = to_visit.pop_front();
+    // Counts the paths from start to end that visit every node in required and none in forbidden.
+    // The nodes are filled in reverse topological order, so nothing recurses however long the
+    // chains are, into a dense table with one row per node and one column per set of required
+    // nodes already visited.
+    fn count_paths(
+        &self,
+        start: &str,
+        end: &str,
+        required: &[&str],
+        forbidden: &[&str],
+    ) -> Result<u64, GraphError> {
+        assert!(
+            required.len() <= MAX_REQUIRED,
+            "At most {} required nodes fit in the table",
+            MAX_REQUIRED
+        );
+
+        let mut required_bits = vec![0usize; self.nodes.len()];
+        for (bit, id) in required.iter().enumerate() {
+            required_bits[self.get_index(id)?] |= 1 << bit;
+        }
+        let all_required = required_bits.iter().fold(0, |mask, bit| mask | bit);
+
+        let mut is_forbidden = vec![false; self.nodes.len()];
+        for id in forbidden {
+            is_forbidden[self.get_index(id)?] = true;
+        }
+
+        let end = self.get_index(end)?;
+        let start = self.get_index(start)?;
+        let order = self.topological_order_from(start)?;
+
+        // paths[node][mask] counts the paths from node to end given mask was visited before node.
+        // Forbidden nodes keep their rows of zeros.
+        let num_masks = 1 << required.len();
+        let mut paths = vec![vec![0u64; num_masks]; self.nodes.len()];
+        for &node in order.iter().rev() {
+            if is_forbidden[node] {
+                continue;
+            }
 
-        match current_node {
-            Some(node) => {
-                if node.is_out() {
-                    paths += 1;
+            for mask in 0..num_masks {
+                let visited = mask | required_bits[node];
+                paths[node][mask] = if node == end {
+                    u64::from(visited == all_required)
                 } else {
-                    // Push all of the connecting nodes in the current node
-                    node.connections_index
+                    self.get_node(node)
+                        .connections_index
                         .iter()
-                        .map(|index| graph.get_node(*index))
-                        .for_each(|node| to_visit.push_back(node));
-                }
+                        .try_fold(0u64, |sum, &child| sum.checked_add(paths[child][visited]))
+                        .ok_or_else(|| self.overflow(node))?
+                };
             }
-            None => {
-                break;
+        }
+
+        Ok(paths[start][0])
+    }
+
+    // Paths to end from every node reachable from start
+    fn paths_to_end(&self, start: usize, end: usize) -> Result<Vec<u64>, GraphError> {
+        let order = self.topological_order_from(start)?;
+        self.count_paths_in_order(&order, end)
+    }
+
+    // The fewest hops from each node to end, None where end can't be reached. Worked backwards over
+    // the topological order from start, so only nodes reachable from start are filled in.
+    fn distances_to_end(&self, start: usize, end: usize) -> Result<Vec<Option<usize>>, GraphError> {
+        let mut distances = vec![None; self.nodes.len()];
+        for &node in self.topological_order_from(start)?.iter().rev() {
+            distances[node] = if node == end {
+                Some(0)
+            } else {
+                self.get_node(node)
+                    .connections_index
+                    .iter()
+                    .filter_map(|&child| distances[child])
+                    .min()
+                    .map(|distance| distance + 1)
+            };
+        }
+        Ok(distances)
+    }
+
+    // Lazily yields every path from start to end, shortest first. Use `take` to limit it.
+    fn paths(&self, start: &str, end: &str) -> Result<PathIter<'_>, GraphError> {
+        let start = self.get_index(start)?;
+        let end = self.get_index(end)?;
+        let distances = self.distances_to_end(start, end)?;
+
+        let mut iter = PathIter {
+            graph: self,
+            end,
+            distances,
+            queue: BinaryHeap::new(),
+            pushed: 0,
+        };
+        iter.push(vec![start]);
+        Ok(iter)
+    }
+
+    // The k shortest paths by hop count, ties in the order the connections are listed
+    fn shortest_paths(
+        &self,
+        start: &str,
+        end: &str,
+        k: usize,
+    ) -> Result<Vec<Vec<usize>>, GraphError> {
+        Ok(self.paths(start, end)?.take(k).collect())
+    }
+
+    // Draws paths uniformly at random: each step picks a child with probability proportional to
+    // the number of paths through it, so every complete path is equally likely
+    fn sample_paths(
+        &self,
+        start: &str,
+        end: &str,
+        samples: usize,
+        rng: &mut Rng,
+    ) -> Result<Vec<Vec<usize>>, GraphError> {
+        let start = self.get_index(start)?;
+        let end = self.get_index(end)?;
+        let counts = self.paths_to_end(start, end)?;
+        if counts[start] == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut paths = Vec::new();
+        for _ in 0..samples {
+            let mut path = vec![start];
+            let mut current = start;
+            while current != end {
+                let mut pick = rng.below(counts[current]);
+                for &child in &self.get_node(current).connections_index {
+                    if pick < counts[child] {
+                        current = child;
+                        break;
+                    }
+                    pick -= counts[child];
+                }
+                path.push(current);
             }
+            paths.push(path);
         }
+
+        Ok(paths)
+    }
+
+    fn path_ids(&self, path: &[usize]) -> Vec<&str> {
+        path.iter()
+            .map(|&index| self.get_node(index).id.as_str())
+            .collect()
     }
+}
+
+// Best first over partial paths, ordered by the length of the shortest path each one can still
+// become. The distances are exact, so complete paths come out in order of length and every partial
+// path taken from the queue leads to one. Among equal lengths the deepest partial path goes first,
+// and then the one queued first, so ties come out in the order the connections are listed and only
+// a few partial paths per hop are ever queued.
+// (shortest complete length, deepest first, queued order, partial path), smallest first
+type QueuedPath = Reverse<(usize, Reverse<usize>, usize, Vec<usize>)>;
+
+struct PathIter<'a> {
+    graph: &'a NodeGraph,
+    end: usize,
+    distances: Vec<Option<usize>>,
+    queue: BinaryHeap<QueuedPath>,
+    pushed: usize,
+}
 
-    paths
+impl PathIter<'_> {
+    // Queues the partial path unless it can't reach the end
+    fn push(&mut self, path: Vec<usize>) {
+        let Some(distance) = self.distances[*path.last().unwrap()] else {
+            return;
+        };
+        let length = path.len() + distance;
+        self.queue
+            .push(Reverse((length, Reverse(path.len()), self.pushed, path)));
+        self.pushed += 1;
+    }
 }
 
-fn recursive_part_2(
-    memo: &mut HashMap<(usize, bool, bool), u64>,
-    fft: bool,
-    dac: bool,
-    to_visit: usize,
-    graph: &NodeGraph,
-) -> u64 {
-    let current_node = graph.get_node(to_visit);
+impl Iterator for PathIter<'_> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Vec<usize>> {
+        while let Some(Reverse((_, _, _, path))) = self.queue.pop() {
+            let last = *path.last().unwrap();
+            if last == self.end {
+                return Some(path);
+            }
 
-    if current_node.id == "out" {
-        return if fft && dac { 1 } else { 0 };
+            for &child in &self.graph.get_node(last).connections_index {
+                let mut extended = path.clone();
+                extended.push(child);
+                self.push(extended);
+            }
+        }
+
+        None
     }
+}
+
+// SplitMix64, seeded so that samples can be reproduced
+struct Rng {
+    state: u64,
+}
 
-    let key = (to_visit, fft, dac);
-    if let Some(&cached) = memo.get(&(to_visit, fft, dac)) {
-        return cached;
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng { state: seed }
     }
 
-    let mut paths = 0;
-    let fft_next = fft || current_node.id == "fft";
-    let dac_next = dac || current_node.id == "dac";
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
 
-    for &child_idx in &current_node.connections_index {
-        paths += recursive_part_2(memo, fft_next, dac_next, child_idx, graph);
+    // Uniform in 0..bound, rejecting the top sliver of the range that would bias the modulo
+    fn below(&mut self, bound: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % bound;
+            }
+        }
     }
+}
 
-    memo.insert(key, paths);
-    paths
+fn part_1(graph: &NodeGraph) -> u64 {
+    graph
+        .count_paths("you", "out", &[], &[])
+        .unwrap_or_else(|error| panic!("{}", error))
 }
 
-fn part_2(input: &str) -> u64 {
-    let mut memo = HashMap::new();
+fn part_2(graph: &NodeGraph) -> u64 {
+    graph
+        .count_paths("svr", "out", &["fft", "dac"], &[])
+        .unwrap_or_else(|error| panic!("{}", error))
+}
 
-    let graph = NodeGraph {
-        nodes: parse_nodes(&input),
+#[derive(Debug, Default)]
+struct Options {
+    parse: ParseOptions,
+    // print this many of the shortest paths for each part
+    list_paths: Option<usize>,
+    // print this many uniformly sampled paths for each part
+    sample_paths: Option<usize>,
+    seed: u64,
+    // write the graph from dot_start as Graphviz DOT to this file
+    dot: Option<String>,
+    dot_start: Option<String>,
+    // required devices to highlight in the DOT export, fft and dac by default
+    highlight: Option<Vec<String>>,
+    // write the adjacency lists as JSON to this file
+    json: Option<String>,
+    // report the devices every path has to go through for each part
+    bottlenecks: bool,
+}
+
+fn number<T: std::str::FromStr>(name: &str, value: String) -> T {
+    value
+        .parse()
+        .unwrap_or_else(|_| panic!("{} needs a number, got {}", name, value))
+}
+
+fn options_from_args(mut args: impl Iterator<Item = String>) -> Options {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| panic!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--implicit-sinks" => options.parse.undefined_targets = UndefinedTargets::ImplicitSink,
+            "--merge-duplicates" => options.parse.duplicates = DuplicateDefinitions::Merge,
+            "--list-paths" => options.list_paths = Some(number(&arg, value())),
+            "--sample-paths" => options.sample_paths = Some(number(&arg, value())),
+            "--seed" => options.seed = number(&arg, value()),
+            "--dot" => options.dot = Some(value()),
+            "--dot-start" => options.dot_start = Some(value()),
+            "--highlight" => {
+                options.highlight = Some(value().split(',').map(str::to_string).collect())
+            }
+            "--json" => options.json = Some(value()),
+            "--bottlenecks" => options.bottlenecks = true,
+            _ => panic!(
+                "Unknown argument {}, expected --implicit-sinks, --merge-duplicates, \
+                 --list-paths N, --sample-paths N, --seed S, --dot FILE, --dot-start ID, \
+                 --highlight ID,ID..., --json FILE or --bottlenecks",
+                arg
+            ),
+        }
+    }
+    options
+}
+
+fn print_bottlenecks(graph: &NodeGraph, options: &Options, start: &str, watched: &[&str]) {
+    if !options.bottlenecks {
+        return;
+    }
+
+    let analyse = || -> Result<(), GraphError> {
+        let bottlenecks = graph.bottlenecks(start, "out")?;
+        println!(
+            "  every path from {} to out passes through [{}]",
+            start,
+            graph.path_ids(&bottlenecks).join(", ")
+        );
+
+        let dominators = graph.dominator_tree(start)?;
+        let post_dominators = graph.post_dominator_tree(start, "out")?;
+        let fractions = graph.path_fractions(start, "out")?;
+        let id = |node: Option<usize>| node.map_or("-", |node| graph.get_node(node).id.as_str());
+        for watched in watched {
+            let node = graph.get_index(watched)?;
+            println!(
+                "  {} is on {:.2}% of paths, entered through {} and left through {}",
+                watched,
+                fractions[node] * 100.0,
+                id(dominators.immediate_dominator(node)),
+                id(post_dominators.immediate_dominator(node))
+            );
+        }
+        Ok(())
     };
-    let start = graph.get_node_from_id("svr");
+    analyse().unwrap_or_else(|error| panic!("{}", error));
+}
+
+fn write_exports(graph: &NodeGraph, options: &Options) {
+    if let Some(filename) = &options.dot {
+        let start = options.dot_start.as_deref().unwrap_or("svr");
+        let highlight: Vec<&str> = match &options.highlight {
+            Some(ids) => ids.iter().map(String::as_str).collect(),
+            None => vec!["fft", "dac"],
+        };
+        let dot = export::to_dot(graph, start, "out", &highlight)
+            .unwrap_or_else(|error| panic!("{}", error));
+        std::fs::write(filename, dot).unwrap_or_else(|e| panic!("Writing {}: {}", filename, e));
+    }
 
-    return recursive_part_2(&mut memo, false, false, start.self_idx, &graph);
+    if let Some(filename) = &options.json {
+        std::fs::write(filename, export::to_json(graph))
+            .unwrap_or_else(|e| panic!("Writing {}: {}", filename, e));
+    }
+}
+
+fn print_paths(graph: &NodeGraph, options: &Options, start: &str) {
+    if let Some(k) = options.list_paths {
+        let paths = graph
+            .shortest_paths(start, "out", k)
+            .unwrap_or_else(|error| panic!("{}", error));
+        for path in paths {
+            println!("  {}", graph.path_ids(&path).join(" -> "));
+        }
+    }
+
+    if let Some(samples) = options.sample_paths {
+        let mut rng = Rng::new(options.seed);
+        let paths = graph
+            .sample_paths(start, "out", samples, &mut rng)
+            .unwrap_or_else(|error| panic!("{}", error));
+        for path in paths {
+            println!("  {}", graph.path_ids(&path).join(" -> "));
+        }
+    }
 }
 
 fn main() {
-    let input = read_input();
+    let options = options_from_args(std::env::args().skip(1));
+    let graph = parse_nodes(&read_input(), &options.parse)
+        .unwrap_or_else(|error| panic!("Invalid input: {}", error));
 
-    let part_1_answer = part_1(&input);
+    let part_1_answer = part_1(&graph);
     println!("Part 1: {}", part_1_answer);
+    print_paths(&graph, &options, "you");
+    print_bottlenecks(&graph, &options, "you", &[]);
 
-    let part_2_answer = part_2(&input);
+    let part_2_answer = part_2(&graph);
 
     println!("Solution: {:?}", part_2_answer);
+    print_paths(&graph, &options, "svr");
+    print_bottlenecks(&graph, &options, "svr", &["fft", "dac"]);
+
+    write_exports(&graph, &options);
 }
 
 #[cfg(test)]
@@ -173,14 +733,258 @@ mod tests {
     fn test_example_1() {
         let input = "aaa: you hhh\nyou: bbb ccc\nbbb: ddd eee\nccc: ddd eee fff\nddd: ggg\neee: out\nfff: out\nggg: out\nhhh: ccc fff iii\niii: out\n";
 
-        let part_1_answer = part_1(&input);
+        let part_1_answer = part_1(&parse_nodes(input, &ParseOptions::default()).unwrap());
 
         assert_eq!(part_1_answer, 5);
 
         let input = "svr: aaa bbb\naaa: fft\nfft: ccc\nbbb: tty\ntty: ccc\nccc: ddd eee\nddd: hub\nhub: fff\neee: dac\ndac: fff\nfff: ggg hhh\nggg: out\nhhh: out\n";
 
-        let part_2_answer = part_2(&input);
+        let part_2_answer = part_2(&parse_nodes(input, &ParseOptions::default()).unwrap());
 
         assert_eq!(part_2_answer, 2);
     }
+
+    #[test]
+    fn test_waypoints() {
+        let input = "svr: aaa bbb\naaa: fft\nfft: ccc\nbbb: tty\ntty: ccc\nccc: ddd eee\nddd: hub\nhub: fff\neee: dac\ndac: fff\nfff: ggg hhh\nggg: out\nhhh: out\n";
+        let graph = parse_nodes(input, &ParseOptions::default()).unwrap();
+
+        assert_eq!(graph.count_paths("svr", "out", &[], &[]), Ok(8));
+        assert_eq!(graph.count_paths("svr", "out", &["fft"], &[]), Ok(4));
+        assert_eq!(graph.count_paths("svr", "out", &["hub", "ggg"], &[]), Ok(2));
+        assert_eq!(graph.count_paths("svr", "out", &["fft"], &["dac"]), Ok(2));
+        assert_eq!(graph.count_paths("svr", "out", &["hub", "dac"], &[]), Ok(0));
+
+        // the end doesn't have to be a sink
+        assert_eq!(graph.count_paths("svr", "fff", &[], &["bbb"]), Ok(2));
+        assert_eq!(graph.count_paths("ccc", "ccc", &[], &[]), Ok(1));
+    }
+
+    #[test]
+    fn test_topological_order() {
+        let input = "aaa: you hhh\nyou: bbb ccc\nbbb: ddd eee\nccc: ddd eee fff\nddd: ggg\neee: out\nfff: out\nggg: out\nhhh: ccc fff iii\niii: out\n";
+        let graph = parse_nodes(input, &ParseOptions::default()).unwrap();
+
+        let aaa = graph.get_node_from_id("aaa").unwrap().self_idx;
+        let order = graph.topological_order_from(aaa).unwrap();
+        assert_eq!(order.len(), graph.nodes.len());
+        let mut position = vec![0; order.len()];
+        order
+            .iter()
+            .enumerate()
+            .for_each(|(i, &node)| position[node] = i);
+        for node in &graph.nodes {
+            for &child in &node.connections_index {
+                assert!(position[node.self_idx] < position[child]);
+            }
+        }
+
+        let you = graph.get_node_from_id("you").unwrap().self_idx;
+        assert_eq!(graph.topological_order_from(you).unwrap().len(), 8);
+    }
+
+    #[test]
+    fn test_cycle() {
+        let input = "you: aaa\naaa: bbb out\nbbb: ccc\nccc: aaa\n";
+        let graph = parse_nodes(input, &ParseOptions::default()).unwrap();
+
+        let error = graph.count_paths("you", "out", &[], &[]).unwrap_err();
+        assert_eq!(
+            error,
+            GraphError::Cycle(vec![
+                "aaa".to_string(),
+                "bbb".to_string(),
+                "ccc".to_string()
+            ])
+        );
+        assert_eq!(error.to_string(), "cycle through aaa -> bbb -> ccc -> aaa");
+        let you = graph.get_node_from_id("you").unwrap().self_idx;
+        assert!(graph.topological_order_from(you).is_err());
+
+        // a cycle out of reach of the start doesn't stop the count
+        assert_eq!(graph.count_paths("out", "out", &[], &[]), Ok(1));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let options = ParseOptions::default();
+
+        let error = parse_nodes("aaa: bbb\nbbb out\n", &options).unwrap_err();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.kind, ParseErrorKind::MissingColon);
+
+        let error = parse_nodes("aaa: out\n\nbbb: ccc out\n", &options).unwrap_err();
+        assert_eq!(error.to_string(), "line 3: output ccc is never defined");
+
+        let error = parse_nodes("aaa: out\nbbb: out\naaa: bbb\n", &options).unwrap_err();
+        assert_eq!(
+            error.kind,
+            ParseErrorKind::DuplicateDefinition("aaa".to_string())
+        );
+
+        let graph = parse_nodes("aaa: out\n", &options).unwrap();
+        assert_eq!(
+            graph.count_paths("zzz", "out", &[], &[]),
+            Err(GraphError::UnknownNode("zzz".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let args = ["--implicit-sinks", "--merge-duplicates"].map(String::from);
+        let options = options_from_args(args.into_iter()).parse;
+
+        let graph = parse_nodes("you: aaa bbb\naaa: out ccc\nyou: ccc aaa\n", &options).unwrap();
+        let ids: Vec<&str> = graph.nodes.iter().map(|node| node.id.as_str()).collect();
+        assert_eq!(ids, vec!["you", "aaa", "out", "bbb", "ccc"]);
+        assert_eq!(
+            graph.get_node_from_id("you").unwrap().connections_index,
+            vec![1, 3, 4]
+        );
+        assert_eq!(graph.count_paths("you", "out", &[], &[]), Ok(1));
+        assert_eq!(graph.count_paths("you", "ccc", &[], &[]), Ok(2));
+
+        // a repeated output on one line is a second edge to it in either mode, while merging only
+        // drops the outputs an earlier line already listed
+        let input = "you: aaa aaa bbb\naaa: out out\nbbb: out\n";
+        for options in [ParseOptions::default(), options] {
+            let graph = parse_nodes(input, &options).unwrap();
+            assert_eq!(
+                graph.get_node_from_id("you").unwrap().connections_index,
+                vec![1, 1, 2]
+            );
+            assert_eq!(graph.count_paths("you", "out", &[], &[]), Ok(5));
+        }
+        let graph =
+            parse_nodes("you: aaa\nyou: aaa bbb bbb\naaa: out\nbbb: out\n", &options).unwrap();
+        assert_eq!(
+            graph.get_node_from_id("you").unwrap().connections_index,
+            vec![1, 2, 2]
+        );
+    }
+
+    #[test]
+    fn test_paths() {
+        let input = "aaa: you hhh\nyou: bbb ccc\nbbb: ddd eee\nccc: ddd eee fff\nddd: ggg\neee: out\nfff: out\nggg: out\nhhh: ccc fff iii\niii: out\n";
+        let graph = parse_nodes(input, &ParseOptions::default()).unwrap();
+
+        let paths: Vec<Vec<&str>> = graph
+            .paths("you", "out")
+            .unwrap()
+            .map(|path| graph.path_ids(&path))
+            .collect();
+        assert_eq!(paths.len(), 5);
+        assert!(paths.windows(2).all(|pair| pair[0].len() <= pair[1].len()));
+        assert_eq!(paths[0], vec!["you", "bbb", "eee", "out"]);
+        assert_eq!(paths[4], vec!["you", "ccc", "ddd", "ggg", "out"]);
+
+        let shortest = graph.shortest_paths("you", "out", 2).unwrap();
+        assert_eq!(shortest.len(), 2);
+        assert!(shortest.iter().all(|path| path.len() == 4));
+
+        assert_eq!(graph.paths("iii", "you").unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_paths_layered() {
+        // 30 layers of 4 devices, each wired to all 4 in the next layer, is 4^30 paths of the same
+        // length, far too many to hold even the partial paths of one layer
+        let (layers, width) = (30, 4);
+        let layer =
+            |i: usize| -> Vec<String> { (0..width).map(|j| format!("l{}n{}", i, j)).collect() };
+        // plus a detour one hop longer, which has to wait behind all of them
+        let mut input = format!(
+            "you: dt0 {}\ndt0: dt1\ndt1: dt2\ndt2: {}\n",
+            layer(0).join(" "),
+            layer(2).join(" ")
+        );
+        for i in 0..layers {
+            let next = if i + 1 == layers {
+                "out".to_string()
+            } else {
+                layer(i + 1).join(" ")
+            };
+            for id in layer(i) {
+                input.push_str(&format!("{}: {}\n", id, next));
+            }
+        }
+        let graph = parse_nodes(&input, &ParseOptions::default()).unwrap();
+
+        let mut paths = graph.paths("you", "out").unwrap();
+        let first: Vec<Vec<usize>> = paths.by_ref().take(5).collect();
+        assert_eq!(first.len(), 5);
+        assert!(first.iter().all(|path| path.len() == layers + 2));
+        assert_eq!(
+            graph.path_ids(&first[0])[..4],
+            ["you", "l0n0", "l1n0", "l2n0"]
+        );
+        assert_eq!(graph.path_ids(&first[1])[layers..], ["l29n1", "out"]);
+        // a handful of siblings per hop, not a layer's worth of partial paths
+        assert!(paths.queue.len() <= 5 * (layers + 2) * (width + 1));
+
+        let shortest = graph.shortest_paths("you", "out", 1000).unwrap();
+        assert!(shortest.iter().all(|path| path.len() == layers + 2));
+        assert!(shortest.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn test_sample_paths() {
+        let input = "aaa: you hhh\nyou: bbb ccc\nbbb: ddd eee\nccc: ddd eee fff\nddd: ggg\neee: out\nfff: out\nggg: out\nhhh: ccc fff iii\niii: out\n";
+        let graph = parse_nodes(input, &ParseOptions::default()).unwrap();
+
+        let all_paths: Vec<Vec<usize>> = graph.paths("you", "out").unwrap().collect();
+        let samples = graph
+            .sample_paths("you", "out", 5000, &mut Rng::new(11))
+            .unwrap();
+
+        // every sample is a real path, and each of the 5 turns up roughly a fifth of the time
+        for path in &all_paths {
+            let hits = samples.iter().filter(|sample| *sample == path).count();
+            assert!(
+                (800..1200).contains(&hits),
+                "{:?} drawn {} times",
+                path,
+                hits
+            );
+        }
+        assert!(samples.iter().all(|sample| all_paths.contains(sample)));
+
+        // the same seed gives the same samples
+        assert_eq!(
+            graph.sample_paths("you", "out", 10, &mut Rng::new(3)),
+            graph.sample_paths("you", "out", 10, &mut Rng::new(3))
+        );
+    }
+
+    #[test]
+    fn test_long_chain() {
+        // deep enough that one stack frame per hop would overflow the test thread's stack
+        let length = 100_000;
+        let mut input = String::from("you: d0\n");
+        for i in 0..length {
+            input.push_str(&format!("d{}: d{} x{}\nx{}: d{}\n", i, i + 1, i, i, i + 1));
+        }
+        input.push_str(&format!("d{}: out\n", length));
+        let graph = parse_nodes(&input, &ParseOptions::default()).unwrap();
+
+        // the chain doubles the path count at every step, which soon no longer fits
+        assert!(matches!(
+            graph.count_paths("you", "out", &["d5"], &[]),
+            Err(GraphError::Overflow(_))
+        ));
+        assert!(matches!(
+            graph.count_paths("you", "out", &[], &[]),
+            Err(GraphError::Overflow(_))
+        ));
+
+        let from_last = "d99990";
+        assert_eq!(
+            graph.count_paths(from_last, "out", &["d99995"], &[]),
+            Ok(1024)
+        );
+        assert_eq!(
+            graph.count_paths(from_last, "out", &[], &["x99995"]),
+            Ok(512)
+        );
+    }
 }
@@ -0,0 +1,330 @@
+// DIMACS CNF encoding of a packing, for handing hard regions to an external SAT solver. Variable
+// i + 1 is true when placement i of `all_placements` is used. Each cell is covered at most once and
+// each shape is placed exactly as many times as the region asks, both as sequential counters.
+
+use std::fmt::Display;
+
+use crate::packing::{Placement, all_placements};
+use crate::{Region, Shape};
+
+#[derive(Debug, Default)]
+pub struct Cnf {
+    pub num_variables: usize,
+    pub clauses: Vec<Vec<i64>>,
+}
+
+impl Cnf {
+    fn new_variable(&mut self) -> i64 {
+        self.num_variables += 1;
+        self.num_variables as i64
+    }
+
+    // Sinz's sequential counter: s[i][j] is true when at least j + 1 of the first i + 1 literals
+    // are, and the count may never pass limit
+    fn at_most(&mut self, literals: &[i64], limit: usize) {
+        let n = literals.len();
+        if limit >= n {
+            return;
+        }
+        if limit == 0 {
+            for &literal in literals {
+                self.clauses.push(vec![-literal]);
+            }
+            return;
+        }
+
+        let counters: Vec<Vec<i64>> = (0..n - 1)
+            .map(|_| (0..limit).map(|_| self.new_variable()).collect())
+            .collect();
+
+        self.clauses.push(vec![-literals[0], counters[0][0]]);
+        for &counter in &counters[0][1..] {
+            self.clauses.push(vec![-counter]);
+        }
+        for i in 1..n - 1 {
+            self.clauses.push(vec![-literals[i], counters[i][0]]);
+            self.clauses.push(vec![-counters[i - 1][0], counters[i][0]]);
+            for j in 1..limit {
+                self.clauses
+                    .push(vec![-literals[i], -counters[i - 1][j - 1], counters[i][j]]);
+                self.clauses.push(vec![-counters[i - 1][j], counters[i][j]]);
+            }
+            self.clauses
+                .push(vec![-literals[i], -counters[i - 1][limit - 1]]);
+        }
+        self.clauses
+            .push(vec![-literals[n - 1], -counters[n - 2][limit - 1]]);
+    }
+
+    fn exactly(&mut self, literals: &[i64], count: usize) {
+        if count > literals.len() {
+            // can never hold
+            self.clauses.push(Vec::new());
+            return;
+        }
+        self.at_most(literals, count);
+        // at least count true is at most n - count false
+        let negated: Vec<i64> = literals.iter().map(|&literal| -literal).collect();
+        self.at_most(&negated, literals.len() - count);
+    }
+}
+
+impl Display for Cnf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "p cnf {} {}", self.num_variables, self.clauses.len())?;
+        for clause in &self.clauses {
+            for literal in clause {
+                write!(f, "{} ", literal)?;
+            }
+            writeln!(f, "0")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ModelError {
+    // neither SATISFIABLE nor UNSATISFIABLE was reported
+    NoStatus,
+    // the solver gave up, such as UNKNOWN on a timeout
+    UnknownStatus(String),
+    InvalidLiteral(String),
+}
+
+impl Display for ModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelError::NoStatus => write!(f, "the solver output has no s line"),
+            ModelError::UnknownStatus(status) => {
+                write!(f, "the solver didn't decide, it reported {}", status)
+            }
+            ModelError::InvalidLiteral(text) => write!(f, "invalid literal {:?}", text),
+        }
+    }
+}
+
+pub struct Encoding {
+    pub cnf: Cnf,
+    // placements[i] is variable i + 1
+    placements: Vec<Placement>,
+}
+
+pub fn encode(region: &Region, shapes: &[Shape]) -> Encoding {
+    let (width, height) = region.area;
+    let placements = all_placements(region, shapes);
+    let mut cnf = Cnf {
+        num_variables: placements.len(),
+        clauses: Vec::new(),
+    };
+
+    let mut covering = vec![Vec::new(); width * height];
+    let mut of_shape = vec![Vec::new(); region.shapes.len()];
+    for (i, placement) in placements.iter().enumerate() {
+        let variable = i as i64 + 1;
+        of_shape[placement.shape].push(variable);
+        for &(row, col) in &placement.cells {
+            covering[row * width + col].push(variable);
+        }
+    }
+
+    for variables in &covering {
+        cnf.at_most(variables, 1);
+    }
+    for (variables, &count) in of_shape.iter().zip(&region.shapes) {
+        cnf.exactly(variables, count as usize);
+    }
+
+    Encoding { cnf, placements }
+}
+
+impl Encoding {
+    // Reads the solver's output in the competition format, an `s SATISFIABLE` or
+    // `s UNSATISFIABLE` line and `v` lines of literals. Returns None for an unsatisfiable region, and
+    // an error for any other status since that is no answer either way.
+    pub fn decode(&self, output: &str) -> Result<Option<Vec<Placement>>, ModelError> {
+        let mut satisfiable = None;
+        let mut chosen = Vec::new();
+        for line in output.lines() {
+            let line = line.trim();
+            if let Some(status) = line.strip_prefix("s ") {
+                satisfiable = match status.trim() {
+                    "SATISFIABLE" => Some(true),
+                    "UNSATISFIABLE" => Some(false),
+                    other => return Err(ModelError::UnknownStatus(other.to_string())),
+                };
+            } else if let Some(literals) = line.strip_prefix("v ") {
+                for literal in literals.split_whitespace() {
+                    let value: i64 = literal
+                        .parse()
+                        .map_err(|_| ModelError::InvalidLiteral(literal.to_string()))?;
+                    if value > 0 && value as usize <= self.placements.len() {
+                        chosen.push(self.placements[value as usize - 1].clone());
+                    }
+                }
+            }
+        }
+
+        match satisfiable {
+            Some(true) => Ok(Some(chosen)),
+            Some(false) => Ok(None),
+            None => Err(ModelError::NoStatus),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packing::verify;
+    use crate::parse_input;
+    use crate::tests::EXAMPLE;
+
+    // Plain DPLL with unit propagation, enough for the small instances here
+    fn solve(cnf: &Cnf) -> Option<Vec<bool>> {
+        fn search(clauses: &[Vec<i64>], assignment: &mut Vec<Option<bool>>) -> bool {
+            let value = |assignment: &[Option<bool>], literal: i64| {
+                assignment[literal.unsigned_abs() as usize].map(|value| value == (literal > 0))
+            };
+
+            let mut all_satisfied = true;
+            for clause in clauses {
+                if clause
+                    .iter()
+                    .any(|&literal| value(assignment, literal) == Some(true))
+                {
+                    continue;
+                }
+                all_satisfied = false;
+                let open: Vec<i64> = clause
+                    .iter()
+                    .copied()
+                    .filter(|&literal| value(assignment, literal).is_none())
+                    .collect();
+                match open.len() {
+                    0 => return false,
+                    1 => {
+                        let variable = open[0].unsigned_abs() as usize;
+                        assignment[variable] = Some(open[0] > 0);
+                        if search(clauses, assignment) {
+                            return true;
+                        }
+                        assignment[variable] = None;
+                        return false;
+                    }
+                    _ => {}
+                }
+            }
+            if all_satisfied {
+                return true;
+            }
+
+            // the placements come first, and the counters follow from them
+            let variable = (1..assignment.len())
+                .find(|&variable| assignment[variable].is_none())
+                .unwrap();
+            for choice in [true, false] {
+                assignment[variable] = Some(choice);
+                if search(clauses, assignment) {
+                    return true;
+                }
+            }
+            assignment[variable] = None;
+            false
+        }
+
+        let mut assignment = vec![None; cnf.num_variables + 1];
+        search(&cnf.clauses, &mut assignment).then(|| {
+            assignment
+                .iter()
+                .map(|value| value.unwrap_or(false))
+                .collect()
+        })
+    }
+
+    fn model(assignment: &[bool]) -> String {
+        let literals: Vec<String> = (1..assignment.len())
+            .map(|variable| {
+                if assignment[variable] {
+                    variable.to_string()
+                } else {
+                    format!("-{}", variable)
+                }
+            })
+            .collect();
+        format!("s SATISFIABLE\nv {} 0\n", literals.join(" "))
+    }
+
+    #[test]
+    fn test_cardinality() {
+        // every assignment of 5 variables against exactly 2
+        for mask in 0..32u32 {
+            let mut cnf = Cnf {
+                num_variables: 5,
+                clauses: Vec::new(),
+            };
+            cnf.exactly(&[1, 2, 3, 4, 5], 2);
+            for variable in 1..=5 {
+                let literal = if mask >> (variable - 1) & 1 == 1 {
+                    variable
+                } else {
+                    -variable
+                };
+                cnf.clauses.push(vec![literal]);
+            }
+            assert_eq!(
+                solve(&cnf).is_some(),
+                mask.count_ones() == 2,
+                "{:05b}",
+                mask
+            );
+        }
+    }
+
+    #[test]
+    fn test_encode() {
+        let (shapes, _) = parse_input(EXAMPLE).unwrap();
+        let region = |area, shapes: &[u32]| Region {
+            area,
+            shapes: shapes.to_vec(),
+        };
+
+        let fits = region((4, 4), &[0, 0, 0, 0, 2, 0]);
+        let encoding = encode(&fits, &shapes);
+        let dimacs = encoding.cnf.to_string();
+        assert!(dimacs.starts_with(&format!(
+            "p cnf {} {}\n",
+            encoding.cnf.num_variables,
+            encoding.cnf.clauses.len()
+        )));
+
+        let assignment = solve(&encoding.cnf).unwrap();
+        let placements = encoding.decode(&model(&assignment)).unwrap().unwrap();
+        assert!(verify(&fits, &shapes, &placements));
+
+        // two 3x3 presents can't both fit in 4x3
+        let too_narrow = region((4, 3), &[0, 0, 0, 0, 0, 2]);
+        assert!(solve(&encode(&too_narrow, &shapes).cnf).is_none());
+    }
+
+    #[test]
+    fn test_decode() {
+        let (shapes, regions) = parse_input(EXAMPLE).unwrap();
+        let encoding = encode(&regions[0], &shapes);
+
+        assert_eq!(encoding.decode("c done\ns UNSATISFIABLE\n"), Ok(None));
+        assert_eq!(encoding.decode("v 1 -2 0\n"), Err(ModelError::NoStatus));
+        assert_eq!(
+            encoding.decode("c timed out\ns UNKNOWN\n"),
+            Err(ModelError::UnknownStatus("UNKNOWN".to_string()))
+        );
+        assert_eq!(
+            encoding.decode("s SATISFIABLE\nv 1 x 0\n"),
+            Err(ModelError::InvalidLiteral("x".to_string()))
+        );
+        let placements = encoding
+            .decode("s SATISFIABLE\nv 1 -2\nv 3 0\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(placements.len(), 2);
+    }
+}
@@ -0,0 +1,299 @@
+// Random packing instances with known answers, for stress testing the checks and the search. Regions
+// that fit are made by dropping presents onto an empty grid and counting what landed. Regions that
+// don't either ask for more cells than they have or can't balance the colours of a checkerboard.
+
+use std::ops::RangeInclusive;
+
+use crate::packing::{Placement, present_area};
+use crate::{Region, Shape, find_shape};
+
+// SplitMix64, seeded so that instances can be reproduced
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in 0..bound, rejecting the top sliver of the range that would bias the modulo
+    fn below(&mut self, bound: usize) -> usize {
+        let bound = bound as u64;
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return (value % bound) as usize;
+            }
+        }
+    }
+
+    fn side(&mut self, sides: &RangeInclusive<usize>) -> usize {
+        sides.start() + self.below(sides.end() - sides.start() + 1)
+    }
+}
+
+// Drops random orientations of random shapes at random spots, keeping those that land on free
+// cells, until a few attempts per cell have been made. The counts are whatever landed, so the
+// returned placements are a packing of the region.
+pub fn solvable(
+    shapes: &[Shape],
+    width: usize,
+    height: usize,
+    rng: &mut Rng,
+) -> (Region, Vec<Placement>) {
+    let orientations: Vec<Vec<Shape>> = (0..shapes.len())
+        .map(|index| find_shape(shapes, index).orientations())
+        .collect();
+    let mut covered = vec![false; width * height];
+    let mut counts = vec![0; shapes.len()];
+    let mut placements = Vec::new();
+
+    for _ in 0..4 * width * height {
+        let index = rng.below(shapes.len());
+        let orientation = &orientations[index][rng.below(orientations[index].len())];
+        let rows = orientation.filled.len();
+        let cols = orientation.filled[0].len();
+        if rows > height || cols > width {
+            continue;
+        }
+        let top = rng.below(height - rows + 1);
+        let left = rng.below(width - cols + 1);
+
+        let cells: Vec<(usize, usize)> = orientation
+            .cells()
+            .iter()
+            .map(|&(row, col)| (top + row, left + col))
+            .collect();
+        if cells.iter().any(|&(row, col)| covered[row * width + col]) {
+            continue;
+        }
+        for &(row, col) in &cells {
+            covered[row * width + col] = true;
+        }
+        counts[index] += 1;
+        placements.push(Placement {
+            shape: index,
+            cells,
+        });
+    }
+
+    let region = Region {
+        area: (width, height),
+        shapes: counts,
+    };
+    (region, placements)
+}
+
+// A region that fits with random presents added until they have more cells than it does
+pub fn oversubscribed(shapes: &[Shape], width: usize, height: usize, rng: &mut Rng) -> Region {
+    let (mut region, _) = solvable(shapes, width, height, rng);
+    while present_area(&region, shapes) <= width * height {
+        region.shapes[rng.below(shapes.len())] += 1;
+    }
+    region
+}
+
+// How many more cells of one colour than the other the shape covers on a checkerboard. Turning,
+// flipping or moving the shape can swap the colours but never changes this.
+fn imbalance(shape: &Shape) -> usize {
+    let black = shape
+        .cells()
+        .iter()
+        .filter(|&&(row, col)| (row + col) % 2 == 0)
+        .count();
+    black.abs_diff(shape.cell_count() - black)
+}
+
+// Whether checkerboard colouring rules the region out. Each present covers its imbalance more of
+// one colour than the other and each free cell one of either, but between them they have to cover
+// one more black cell than white in a region with an odd number of cells and as many of each in
+// one with an even number. This can fail even when the presents exactly fill the region.
+pub fn parity_obstructed(region: &Region, shapes: &[Shape]) -> bool {
+    let (width, height) = region.area;
+    let area = width * height;
+    let cells = present_area(region, shapes);
+    if cells > area {
+        return false;
+    }
+    let free = area - cells;
+    let wanted = (area % 2) as i64;
+
+    // reachable[total + offset] for every total of the presents' signed imbalances
+    let offset: usize = region
+        .shapes
+        .iter()
+        .enumerate()
+        .map(|(index, &count)| count as usize * imbalance(find_shape(shapes, index)))
+        .sum();
+    let mut reachable = vec![false; 2 * offset + 1];
+    reachable[offset] = true;
+    for (index, &count) in region.shapes.iter().enumerate() {
+        let step = imbalance(find_shape(shapes, index));
+        for _ in 0..count {
+            reachable = (0..reachable.len())
+                .map(|total| {
+                    (total >= step && reachable[total - step])
+                        || (total + step < reachable.len() && reachable[total + step])
+                })
+                .collect();
+        }
+    }
+
+    !reachable.iter().enumerate().any(|(total, &reached)| {
+        reached && (wanted - (total as i64 - offset as i64)).abs() <= free as i64
+    })
+}
+
+// A region whose presents would fill it if only the colours worked out, made from the shapes that
+// cover more of one colour than the other. None if there are no such shapes or no size within
+// sides was found to work.
+pub fn parity_region(
+    shapes: &[Shape],
+    sides: &RangeInclusive<usize>,
+    rng: &mut Rng,
+) -> Option<Region> {
+    let lopsided: Vec<usize> = (0..shapes.len())
+        .filter(|&index| imbalance(find_shape(shapes, index)) > 1)
+        .collect();
+    if lopsided.is_empty() {
+        return None;
+    }
+
+    for _ in 0..1000 {
+        let (width, height) = (rng.side(sides), rng.side(sides));
+        let mut region = Region {
+            area: (width, height),
+            shapes: vec![0; shapes.len()],
+        };
+        loop {
+            let index = lopsided[rng.below(lopsided.len())];
+            region.shapes[index] += 1;
+            if present_area(&region, shapes) > width * height {
+                region.shapes[index] -= 1;
+                break;
+            }
+        }
+        if parity_obstructed(&region, shapes) {
+            return Some(region);
+        }
+    }
+    None
+}
+
+// count regions with sides drawn from sides, taking turns between one that fits, one with too many
+// presents and one the colouring rules out, with whether each fits
+pub fn generate(
+    shapes: &[Shape],
+    count: usize,
+    sides: RangeInclusive<usize>,
+    rng: &mut Rng,
+) -> Vec<(Region, bool)> {
+    (0..count)
+        .map(|i| {
+            let (width, height) = (rng.side(&sides), rng.side(&sides));
+            match i % 3 {
+                0 => (solvable(shapes, width, height, rng).0, true),
+                1 => (oversubscribed(shapes, width, height, rng), false),
+                _ => match parity_region(shapes, &sides, rng) {
+                    Some(region) => (region, false),
+                    None => (oversubscribed(shapes, width, height, rng), false),
+                },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packing::{Budget, Status, pack, verify};
+    use crate::parse_input;
+    use crate::tests::EXAMPLE;
+
+    #[test]
+    fn test_solvable() {
+        let (shapes, _) = parse_input(EXAMPLE).unwrap();
+        let mut rng = Rng::new(12);
+        for _ in 0..20 {
+            let (width, height) = (rng.side(&(3..=10)), rng.side(&(3..=10)));
+            let (region, placements) = solvable(&shapes, width, height, &mut rng);
+            assert!(verify(&region, &shapes, &placements), "{:?}", region);
+
+            let region = oversubscribed(&shapes, width, height, &mut rng);
+            assert!(present_area(&region, &shapes) > width * height);
+        }
+    }
+
+    #[test]
+    fn test_parity() {
+        let (shapes, _) = parse_input(EXAMPLE).unwrap();
+        let region = |area, shapes: &[u32]| Region {
+            area,
+            shapes: shapes.to_vec(),
+        };
+        // three 5s cover 21 cells but 3 more or fewer black than white each, never the 1 needed
+        assert_eq!(imbalance(&shapes[5]), 3);
+        assert!(parity_obstructed(
+            &region((7, 3), &[0, 0, 0, 0, 0, 3]),
+            &shapes
+        ));
+        assert!(!parity_obstructed(
+            &region((7, 3), &[0, 0, 0, 0, 1, 2]),
+            &shapes
+        ));
+        assert!(!parity_obstructed(
+            &region((4, 4), &[0, 0, 0, 0, 2, 0]),
+            &shapes
+        ));
+
+        // the classic: T tetrominoes can't tile 4x3, though 3 of them have the cells
+        let t = [Shape {
+            index: 0,
+            filled: vec![vec![true, true, true], vec![false, true, false]],
+        }];
+        assert!(parity_obstructed(&region((4, 3), &[3]), &t));
+        assert!(!parity_obstructed(&region((4, 4), &[4]), &t));
+
+        let mut rng = Rng::new(3);
+        let obstructed = parity_region(&shapes, &(3..=8), &mut rng).unwrap();
+        assert!(parity_obstructed(&obstructed, &shapes));
+        assert_eq!(
+            pack(&obstructed, &shapes, &Budget::default()),
+            Status::DoesNotFit
+        );
+    }
+
+    #[test]
+    fn test_generate() {
+        let (shapes, _) = parse_input(EXAMPLE).unwrap();
+        let generated = generate(&shapes, 6, 4..=9, &mut Rng::new(7));
+        assert_eq!(generated.len(), 6);
+        assert_eq!(
+            generated.iter().map(|(_, fits)| *fits).collect::<Vec<_>>(),
+            vec![true, false, false, true, false, false]
+        );
+
+        // the same seed gives the same regions, and they read back in
+        let again = generate(&shapes, 6, 4..=9, &mut Rng::new(7));
+        let text: String = generated
+            .iter()
+            .map(|(region, _)| format!("{}\n", region))
+            .collect();
+        let input = format!("{}\n{}", EXAMPLE.split("4x4").next().unwrap(), text);
+        let (_, regions) = parse_input(&input).unwrap();
+        assert_eq!(regions.len(), 6);
+        for (region, (expected, _)) in regions.iter().zip(&again) {
+            assert_eq!(region, expected);
+        }
+    }
+}
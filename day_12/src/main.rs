@@ -1,84 +1,422 @@
+mod cnf;
+mod generate;
+mod packing;
+mod render;
+
+use packing::{Budget, Placement, Status};
+
+use std::fmt::Display;
 use std::fs::File;
-use std::io::Read;
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Write};
+use std::str::FromStr;
+use std::time::Duration;
 
+// Reads the whole puzzle input from a file, or from stdin when the name is "-"
 fn read_input(filename: &str) -> String {
     let mut contents = String::new();
 
-    let _ = File::read_to_string(&mut File::open(filename).unwrap(), &mut contents);
+    let result = if filename == "-" {
+        std::io::stdin().read_to_string(&mut contents)
+    } else {
+        File::open(filename).and_then(|mut file| file.read_to_string(&mut contents))
+    };
+    result.unwrap_or_else(|error| panic!("Couldn't read {}: {}", filename, error));
 
     contents
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct Shape {
     index: usize,
     filled: Vec<Vec<bool>>,
 }
 
-#[derive(Debug)]
+impl Shape {
+    fn cell_count(&self) -> usize {
+        self.filled
+            .iter()
+            .flatten()
+            .filter(|&&filled| filled)
+            .count()
+    }
+
+    // (row, column) of every filled cell in reading order
+    fn cells(&self) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        for (row, line) in self.filled.iter().enumerate() {
+            for (col, &filled) in line.iter().enumerate() {
+                if filled {
+                    cells.push((row, col));
+                }
+            }
+        }
+        cells
+    }
+
+    // The smallest rectangle holding every filled cell, with all rows the same length
+    fn trimmed(&self) -> Shape {
+        let cells = self.cells();
+        let rows = cells.iter().map(|cell| cell.0);
+        let cols = cells.iter().map(|cell| cell.1);
+        let (Some(top), Some(bottom)) = (rows.clone().min(), rows.max()) else {
+            return Shape {
+                index: self.index,
+                filled: Vec::new(),
+            };
+        };
+        let (left, right) = (cols.clone().min().unwrap(), cols.max().unwrap());
+
+        let mut filled = vec![vec![false; right - left + 1]; bottom - top + 1];
+        for (row, col) in cells {
+            filled[row - top][col - left] = true;
+        }
+        Shape {
+            index: self.index,
+            filled,
+        }
+    }
+
+    // A quarter turn clockwise
+    fn rotated(&self) -> Shape {
+        let trimmed = self.trimmed();
+        let height = trimmed.filled.len();
+        let width = trimmed.filled.first().map_or(0, Vec::len);
+        Shape {
+            index: self.index,
+            filled: (0..width)
+                .map(|row| {
+                    (0..height)
+                        .map(|col| trimmed.filled[height - 1 - col][row])
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    // Mirrored left to right
+    fn reflected(&self) -> Shape {
+        let trimmed = self.trimmed();
+        Shape {
+            index: self.index,
+            filled: trimmed
+                .filled
+                .iter()
+                .map(|row| row.iter().rev().copied().collect())
+                .collect(),
+        }
+    }
+
+    // The distinct trimmed results of the 4 rotations of the shape and of its mirror image
+    fn orientations(&self) -> Vec<Shape> {
+        let mut orientations: Vec<Shape> = Vec::new();
+        for mut current in [self.trimmed(), self.reflected()] {
+            for _ in 0..4 {
+                if !orientations
+                    .iter()
+                    .any(|seen| seen.filled == current.filled)
+                {
+                    orientations.push(current.clone());
+                }
+                current = current.rotated();
+            }
+        }
+        orientations
+    }
+
+    // The same grid for every orientation of the same shape: the smallest of them
+    fn canonical(&self) -> Vec<Vec<bool>> {
+        self.orientations()
+            .into_iter()
+            .map(|orientation| orientation.filled)
+            .min()
+            .unwrap()
+    }
+}
+
+// Shapes are equal when one can be turned or flipped into the other, whatever their indices
+impl PartialEq for Shape {
+    fn eq(&self, other: &Self) -> bool {
+        self.canonical() == other.canonical()
+    }
+}
+
+impl Eq for Shape {}
+
+// The `index:` line and rows of # and . that the shape was read from
+impl Display for Shape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}:", self.index)?;
+        for row in &self.filled {
+            let row: String = row
+                .iter()
+                .map(|&filled| if filled { '#' } else { '.' })
+                .collect();
+            writeln!(f, "{}", row)?;
+        }
+        Ok(())
+    }
+}
+
+impl Hash for Shape {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.canonical().hash(state);
+    }
+}
+
+#[derive(Debug, PartialEq)]
 struct Region {
+    // (width, height)
     area: (usize, usize),
+    // how many presents of each shape index
     shapes: Vec<u32>,
 }
 
-fn parse_input() -> (Vec<Shape>, Vec<Region>) {
-    let mut shapes = Vec::new();
+// `WxH: count count ...`, as in the input
+impl Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}:", self.area.0, self.area.1)?;
+        for count in &self.shapes {
+            write!(f, " {}", count)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ParseErrorKind {
+    InvalidShapeIndex(String),
+    DuplicateShape(usize),
+    EmptyShape(usize),
+    InvalidCell(char),
+    // a row of # and . with no `index:` line above it
+    RowOutsideShape,
+    ShapeAfterRegions,
+    InvalidSize(String),
+    InvalidCount(String),
+    UnknownShape(usize),
+}
+
+#[derive(Debug, PartialEq)]
+struct ParseError {
+    line: usize,
+    kind: ParseErrorKind,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            ParseErrorKind::InvalidShapeIndex(text) => write!(f, "invalid shape index {}", text),
+            ParseErrorKind::DuplicateShape(index) => write!(f, "shape {} is defined twice", index),
+            ParseErrorKind::EmptyShape(index) => write!(f, "shape {} has no cells", index),
+            ParseErrorKind::InvalidCell(c) => write!(f, "expected # or ., found {:?}", c),
+            ParseErrorKind::RowOutsideShape => write!(f, "shape row before any `index:` line"),
+            ParseErrorKind::ShapeAfterRegions => write!(f, "shapes must come before regions"),
+            ParseErrorKind::InvalidSize(text) => write!(f, "expected WxH, found {:?}", text),
+            ParseErrorKind::InvalidCount(text) => write!(f, "invalid present count {:?}", text),
+            ParseErrorKind::UnknownShape(index) => write!(f, "no shape {} was defined", index),
+        }
+    }
+}
+
+// `index:` opening a shape block
+fn parse_shape_index(index: &str) -> Result<usize, ParseErrorKind> {
+    index
+        .trim()
+        .parse()
+        .map_err(|_| ParseErrorKind::InvalidShapeIndex(index.trim().to_string()))
+}
+
+fn parse_shape_row(line: &str) -> Result<Vec<bool>, ParseErrorKind> {
+    line.trim()
+        .chars()
+        .map(|c| match c {
+            '#' => Ok(true),
+            '.' => Ok(false),
+            _ => Err(ParseErrorKind::InvalidCell(c)),
+        })
+        .collect()
+}
+
+// `WxH: count count ...`
+fn parse_region(size: &str, counts: &str) -> Result<Region, ParseErrorKind> {
+    let invalid_size = || ParseErrorKind::InvalidSize(size.trim().to_string());
+    let (width, height) = size.trim().split_once('x').ok_or_else(invalid_size)?;
+    let width = width.parse().map_err(|_| invalid_size())?;
+    let height = height.parse().map_err(|_| invalid_size())?;
+
+    let shapes = counts
+        .split_whitespace()
+        .map(|count| {
+            count
+                .parse()
+                .map_err(|_| ParseErrorKind::InvalidCount(count.to_string()))
+        })
+        .collect::<Result<Vec<u32>, _>>()?;
+
+    Ok(Region {
+        area: (width, height),
+        shapes,
+    })
+}
+
+// Shape blocks of an `index:` line and rows of # and ., separated by blank lines, followed by one
+// `WxH: counts` line per region. A line with a colon is a region when the part before the colon
+// has an x in it, and a shape index otherwise.
+fn parse_input(input: &str) -> Result<(Vec<Shape>, Vec<Region>), ParseError> {
+    let mut shapes: Vec<Shape> = Vec::new();
     let mut regions = Vec::new();
+    // the shape being read and the line its index was on
+    let mut current: Option<(Shape, usize)> = None;
+
+    fn finish(
+        current: &mut Option<(Shape, usize)>,
+        shapes: &mut Vec<Shape>,
+    ) -> Result<(), ParseError> {
+        let Some((shape, line)) = current.take() else {
+            return Ok(());
+        };
+        let error = |kind| Err(ParseError { line, kind });
+        if shape.cell_count() == 0 {
+            return error(ParseErrorKind::EmptyShape(shape.index));
+        }
+        if shapes.iter().any(|other| other.index == shape.index) {
+            return error(ParseErrorKind::DuplicateShape(shape.index));
+        }
+        shapes.push(shape);
+        Ok(())
+    }
 
-    let input = read_input("shapes.txt");
-    let mut index: i32 = -1;
-    let mut filled: Vec<Vec<bool>> = Vec::new();
-    for line in input.lines() {
-        let option = line.split_once(':');
-        match option {
-            Some((index_str, _)) => {
-                if index != -1 {
-                    shapes.push(Shape {
-                        index: index as usize,
-                        filled: filled.clone(),
-                    });
-                    filled.clear();
+    for (i, line) in input.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |kind| ParseError {
+            line: line_number,
+            kind,
+        };
+
+        if line.trim().is_empty() {
+            finish(&mut current, &mut shapes)?;
+            continue;
+        }
+
+        match line.split_once(':') {
+            Some((size, counts)) if size.contains('x') => {
+                finish(&mut current, &mut shapes)?;
+                let region = parse_region(size, counts).map_err(error)?;
+                if let Some(index) = (0..region.shapes.len())
+                    .find(|&index| !shapes.iter().any(|shape| shape.index == index))
+                {
+                    return Err(error(ParseErrorKind::UnknownShape(index)));
                 }
-                println!("Index {}", index_str);
-                index = index_str.parse().unwrap();
+                regions.push(region);
+            }
+            Some((index, rest)) => {
+                if !regions.is_empty() {
+                    return Err(error(ParseErrorKind::ShapeAfterRegions));
+                }
+                finish(&mut current, &mut shapes)?;
+                let index = parse_shape_index(index).map_err(error)?;
+                let mut shape = Shape {
+                    index,
+                    filled: Vec::new(),
+                };
+                // allow the first row on the same line as the index
+                if !rest.trim().is_empty() {
+                    shape.filled.push(parse_shape_row(rest).map_err(error)?);
+                }
+                current = Some((shape, line_number));
             }
             None => {
-                filled.push(line.chars().map(|char| char == '#').collect());
+                if !regions.is_empty() {
+                    return Err(error(ParseErrorKind::ShapeAfterRegions));
+                }
+                let Some((shape, _)) = current.as_mut() else {
+                    return Err(error(ParseErrorKind::RowOutsideShape));
+                };
+                shape.filled.push(parse_shape_row(line).map_err(error)?);
             }
         }
     }
+    finish(&mut current, &mut shapes)?;
+
+    Ok((shapes, regions))
+}
 
-    let input = read_input("regions.txt");
-    for line in input.lines() {
-        let (area, shape_nums) = line.split_once(':').unwrap();
-        let (height, width) = area.split_once('x').unwrap();
+fn find_shape(shapes: &[Shape], index: usize) -> &Shape {
+    shapes
+        .iter()
+        .find(|shape| shape.index == index)
+        .unwrap_or_else(|| panic!("Region needs shape {} which wasn't parsed", index))
+}
+
+// Lays each present's bounding box out in shelves across the region, tallest first. Boxes never
+// overlap, so if the shelves fit then so do the presents, and this is where they go.
+fn shelf_packing(region: &Region, shapes: &[Shape]) -> Option<Vec<Placement>> {
+    let (width, height) = region.area;
 
-        let shape_vec: Vec<u32> = shape_nums
-            .split_whitespace()
-            .map(|string| string.parse().unwrap())
-            .collect();
+    // each present turned to be as flat as it can be while still fitting across
+    let mut boxes: Vec<Shape> = Vec::new();
+    for (index, &count) in region.shapes.iter().enumerate() {
+        let trimmed = find_shape(shapes, index).trimmed();
+        let turned = trimmed.rotated();
+        let flat = if turned.filled.len() < trimmed.filled.len() && turned.filled[0].len() <= width
+        {
+            turned
+        } else {
+            trimmed
+        };
+        if flat.filled.first().map_or(0, Vec::len) > width {
+            return None;
+        }
+        boxes.extend((0..count).map(|_| flat.clone()));
+    }
+    boxes.sort_by_key(|present| std::cmp::Reverse(present.filled.len()));
 
-        regions.push(Region {
-            area: (height.parse().unwrap(), width.parse().unwrap()),
-            shapes: shape_vec,
+    let mut placements = Vec::new();
+    let mut shelf_top = 0;
+    let mut shelf_height = 0;
+    let mut shelf_width = 0;
+    for present in boxes {
+        let (box_width, box_height) = (present.filled[0].len(), present.filled.len());
+        if shelf_width + box_width > width {
+            shelf_top += shelf_height;
+            shelf_width = 0;
+            shelf_height = 0;
+        }
+        placements.push(Placement {
+            shape: present.index,
+            cells: present
+                .cells()
+                .iter()
+                .map(|&(row, col)| (shelf_top + row, shelf_width + col))
+                .collect(),
         });
+        shelf_width += box_width;
+        shelf_height = shelf_height.max(box_height);
     }
-    println!("Shapes: {:?}, Regions {:?}", shapes, regions);
-    (shapes, regions)
+
+    (shelf_top + shelf_height <= height).then_some(placements)
 }
 
-fn trivial_check(regions: &Vec<Region>) -> (Vec<usize>, Vec<usize>, Vec<usize>) {
+// Fails regions where the presents have more cells than the region, passes those where their
+// bounding boxes fit side by side, and leaves the rest to a real search
+fn trivial_check(regions: &[Region], shapes: &[Shape]) -> (Vec<usize>, Vec<usize>, Vec<usize>) {
     let mut trivial_fail = Vec::new();
     let mut trivial_pass = Vec::new();
     let mut complicated = Vec::new();
     for (i, region) in regions.iter().enumerate() {
         let area = region.area.0 * region.area.1;
+        let present_area: usize = region
+            .shapes
+            .iter()
+            .enumerate()
+            .map(|(index, &count)| count as usize * find_shape(shapes, index).cell_count())
+            .sum();
 
-        if region.shapes.iter().sum::<u32>() * 7 >= area as u32 {
+        if present_area > area {
             trivial_fail.push(i);
-        } else if region.shapes.iter().sum::<u32>()
-            <= ((region.area.0 / 3) * (region.area.1 / 3)) as u32
-        {
+        } else if shelf_packing(region, shapes).is_some() {
             trivial_pass.push(i);
         } else {
             complicated.push(i);
@@ -88,15 +426,348 @@ fn trivial_check(regions: &Vec<Region>) -> (Vec<usize>, Vec<usize>, Vec<usize>)
     (trivial_fail, trivial_pass, complicated)
 }
 
+#[derive(Debug)]
+struct Options {
+    // the puzzle input, or - for stdin
+    filename: String,
+    // print every region that fits with its presents drawn in
+    render: bool,
+    // write every region that fits as region_N.ppm in this directory
+    ppm: Option<String>,
+    // write every region the area checks can't decide as region_N.cnf in this directory, and skip
+    // the search
+    cnf: Option<String>,
+    // a region and the file holding a SAT solver's output for its CNF, read instead of searching
+    model: Option<(usize, String)>,
+    budget: Budget,
+    // how many regions to search at once, one per core by default
+    threads: Option<usize>,
+    // print the input's shapes with this many random regions of known answer instead of solving
+    generate: Option<usize>,
+    seed: u64,
+}
+
+fn number<T: FromStr>(name: &str, value: String) -> T {
+    value
+        .parse()
+        .unwrap_or_else(|_| panic!("{} needs a number, got {}", name, value))
+}
+
+fn options_from_args(mut args: impl Iterator<Item = String>) -> Options {
+    let mut options = Options {
+        filename: "input.txt".to_string(),
+        render: false,
+        ppm: None,
+        cnf: None,
+        model: None,
+        budget: Budget::default(),
+        threads: None,
+        generate: None,
+        seed: 0,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| panic!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--render" => options.render = true,
+            "--ppm" => options.ppm = Some(value()),
+            "--cnf" => options.cnf = Some(value()),
+            "--model" => {
+                let region = number(&arg, value());
+                options.model = Some((region, value()));
+            }
+            "--nodes" => options.budget.nodes = Some(number(&arg, value())),
+            "--seconds" => {
+                options.budget.time = Some(Duration::from_secs_f64(number(&arg, value())))
+            }
+            "--threads" => options.threads = Some(number(&arg, value())),
+            "--generate" => options.generate = Some(number(&arg, value())),
+            "--seed" => options.seed = number(&arg, value()),
+            _ if arg.starts_with("--") => panic!(
+                "Unknown argument {}, expected an input file, --render, --ppm DIR, --cnf DIR, \
+                 --model REGION FILE, --nodes N, --seconds S, --threads N, --generate N or --seed S",
+                arg
+            ),
+            _ => options.filename = arg,
+        }
+    }
+    options
+}
+
+// Sides of the regions --generate makes
+const GENERATED_SIDES: std::ops::RangeInclusive<usize> = 4..=12;
+
 fn main() {
-    let (shapes, regions) = parse_input();
+    let options = options_from_args(std::env::args().skip(1));
+    let (shapes, regions) = parse_input(&read_input(&options.filename))
+        .unwrap_or_else(|error| panic!("Invalid input: {}", error));
 
-    let (fail, pass, complicated) = trivial_check(&regions);
+    // checked before the search so a typo doesn't cost a whole run
+    if let Some((i, _)) = options.model
+        && i >= regions.len()
+    {
+        eprintln!(
+            "--model REGION FILE: there is no region {}, the input has {} regions numbered from 0",
+            i,
+            regions.len()
+        );
+        std::process::exit(2);
+    }
 
+    if let Some(count) = options.generate {
+        let mut rng = generate::Rng::new(options.seed);
+        let generated = generate::generate(&shapes, count, GENERATED_SIDES, &mut rng);
+        for shape in &shapes {
+            println!("{}", shape);
+        }
+        for (region, _) in &generated {
+            println!("{}", region);
+        }
+        // what solving the output should report, kept off stdout so the output is a valid input
+        let fits = generated.iter().filter(|(_, fits)| *fits).count();
+        eprintln!("Regions that fit: {}", fits);
+        return;
+    }
+
+    let (fail, pass, complicated) = trivial_check(&regions, &shapes);
     println!(
-        "Fail: {}, Pass: {}, Complicated: {}",
+        "{} shapes and {} regions: {} too small, {} fit side by side, {} to search",
+        shapes.len(),
+        regions.len(),
         fail.len(),
         pass.len(),
         complicated.len()
     );
+
+    // the hard regions go to an external SAT solver instead of the search, which might never finish
+    // on them
+    if options.cnf.is_some() || options.model.is_some() {
+        if let Some(directory) = &options.cnf {
+            for &i in &complicated {
+                let path = std::path::Path::new(directory).join(format!("region_{}.cnf", i));
+                let encoding = cnf::encode(&regions[i], &shapes);
+                std::fs::write(&path, encoding.cnf.to_string())
+                    .unwrap_or_else(|error| panic!("Couldn't write {}: {}", path.display(), error));
+            }
+        }
+
+        if let Some((i, filename)) = &options.model {
+            let region = &regions[*i];
+            let placements = cnf::encode(region, &shapes)
+                .decode(&read_input(filename))
+                .unwrap_or_else(|error| panic!("Invalid model in {}: {}", filename, error));
+            match placements {
+                Some(placements) if packing::verify(region, &shapes, &placements) => {
+                    println!("Region {} fits:", i);
+                    print!("{}", render::to_ascii(region, &placements));
+                }
+                Some(_) => println!("The model for region {} isn't a valid packing", i),
+                None => println!("Region {} does not fit", i),
+            }
+        }
+        return;
+    }
+
+    let threads = options
+        .threads
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |threads| threads.get()));
+    let to_search: Vec<&Region> = complicated.iter().map(|&i| &regions[i]).collect();
+    let searched = packing::pack_all(&to_search, &shapes, &options.budget, threads, |done| {
+        eprint!("\rSearched {} of {} regions", done, to_search.len());
+        std::io::stderr().flush().unwrap();
+    });
+    eprintln!();
+
+    let mut statuses = vec![Status::DoesNotFit; regions.len()];
+    for &i in &pass {
+        statuses[i] = Status::Fits(shelf_packing(&regions[i], &shapes).unwrap());
+    }
+    for (&i, status) in complicated.iter().zip(searched) {
+        statuses[i] = status;
+    }
+
+    for (i, (region, status)) in regions.iter().zip(&statuses).enumerate() {
+        let Status::Fits(placements) = status else {
+            continue;
+        };
+        if options.render {
+            println!("Region {}:", i);
+            print!("{}", render::to_ascii(region, placements));
+        }
+        if let Some(directory) = &options.ppm {
+            let path = std::path::Path::new(directory).join(format!("region_{}.ppm", i));
+            std::fs::write(&path, render::to_ppm(region, placements, 16))
+                .unwrap_or_else(|error| panic!("Couldn't write {}: {}", path.display(), error));
+        }
+    }
+
+    let count =
+        |wanted: fn(&Status) -> bool| statuses.iter().filter(|&status| wanted(status)).count();
+    let fits = count(|status| matches!(status, Status::Fits(_)));
+    let unknown = count(|status| *status == Status::Unknown);
+    println!(
+        "Fits: {}, Does not fit: {}, Unknown: {}",
+        fits,
+        count(|status| *status == Status::DoesNotFit),
+        unknown
+    );
+    if unknown > 0 {
+        println!("Raise --nodes or --seconds to settle the unknown regions");
+    }
+    println!("Regions that fit: {}", fits);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    pub const EXAMPLE: &str = "0:\n###\n##.\n##.\n\n1:\n###\n##.\n.##\n\n2:\n.##\n###\n##.\n\n3:\n##.\n###\n##.\n\n4:\n###\n#..\n###\n\n5:\n###\n.#.\n###\n\n4x4: 0 0 0 0 2 0\n12x5: 1 0 1 0 2 2\n12x5: 1 0 1 0 3 2\n";
+
+    fn example_shapes() -> Vec<Shape> {
+        parse_input(EXAMPLE).unwrap().0
+    }
+
+    fn shape(rows: &[&str]) -> Shape {
+        Shape {
+            index: 0,
+            filled: rows
+                .iter()
+                .map(|row| row.chars().map(|c| c == '#').collect())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_orientations() {
+        let shapes = example_shapes();
+        // the 4 with its gap is symmetric under a flip, the 5 under a flip and a half turn
+        assert_eq!(shapes[4].orientations().len(), 4);
+        assert_eq!(shapes[5].orientations().len(), 2);
+        assert_eq!(shapes[0].orientations().len(), 8);
+        assert!(shapes.iter().all(|shape| shape.cell_count() == 7));
+
+        let l = shape(&["#..", "#..", "##."]);
+        assert_eq!(l.rotated().filled, shape(&["###", "#.."]).filled);
+        assert_eq!(l.reflected().filled, shape(&[".#", ".#", "##"]).filled);
+    }
+
+    #[test]
+    fn test_trimmed() {
+        let padded = shape(&["....", ".#..", ".##", ""]);
+        assert_eq!(padded.trimmed().filled, shape(&["#.", "##"]).filled);
+        assert_eq!(padded.cell_count(), 3);
+        assert_eq!(
+            shape(&["...", ""]).trimmed().filled,
+            Vec::<Vec<bool>>::new()
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let (shapes, regions) = parse_input(EXAMPLE).unwrap();
+        assert_eq!(shapes.len(), 6);
+        assert_eq!(shapes[5].index, 5);
+        assert!(shapes.iter().all(|shape| shape.filled.len() == 3));
+        assert_eq!(
+            regions[1],
+            Region {
+                area: (12, 5),
+                shapes: vec![1, 0, 1, 0, 2, 2]
+            }
+        );
+
+        // the last shape needs no blank line after it, and regions are optional
+        let (shapes, regions) = parse_input("0:\n#.\n##").unwrap();
+        assert_eq!(shapes[0].filled, vec![vec![true, false], vec![true, true]]);
+        assert!(regions.is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input| parse_input(input).unwrap_err();
+        let kind = |line, kind| ParseError { line, kind };
+
+        assert_eq!(
+            error("a:\n#"),
+            kind(1, ParseErrorKind::InvalidShapeIndex("a".to_string()))
+        );
+        assert_eq!(error("0:\n#o"), kind(2, ParseErrorKind::InvalidCell('o')));
+        assert_eq!(
+            error("0:\n#\n\n0:\n#"),
+            kind(4, ParseErrorKind::DuplicateShape(0))
+        );
+        assert_eq!(error("0:\n\n1:\n#"), kind(1, ParseErrorKind::EmptyShape(0)));
+        assert_eq!(error("##\n"), kind(1, ParseErrorKind::RowOutsideShape));
+        assert_eq!(
+            error("0:\n#\n\n1x1: 1\n1:\n#"),
+            kind(5, ParseErrorKind::ShapeAfterRegions)
+        );
+        assert_eq!(
+            error("0:\n#\n\n1y1: 1"),
+            kind(4, ParseErrorKind::InvalidShapeIndex("1y1".to_string()))
+        );
+        assert_eq!(
+            error("0:\n#\n\n1xa: 1"),
+            kind(4, ParseErrorKind::InvalidSize("1xa".to_string()))
+        );
+        assert_eq!(
+            error("0:\n#\n\n2x2: -1"),
+            kind(4, ParseErrorKind::InvalidCount("-1".to_string()))
+        );
+        assert_eq!(
+            error("0:\n#\n\n2x2: 1 1"),
+            kind(4, ParseErrorKind::UnknownShape(1))
+        );
+    }
+
+    #[test]
+    fn test_trivial_check() {
+        let shapes = example_shapes();
+        let region = |area, shapes: &[u32]| Region {
+            area,
+            shapes: shapes.to_vec(),
+        };
+        let regions = [
+            region((4, 4), &[0, 0, 0, 0, 2, 0]),
+            region((12, 5), &[1, 0, 1, 0, 2, 2]),
+            region((12, 5), &[1, 0, 1, 0, 3, 2]),
+            region((4, 4), &[1, 1, 1, 0, 0, 0]),
+            region((9, 6), &[1, 1, 1, 1, 1, 1]),
+            region((12, 5), &[0, 0, 0, 0, 0, 9]),
+        ];
+
+        let (fail, pass, complicated) = trivial_check(&regions, &shapes);
+        assert_eq!(fail, vec![3, 5]);
+        assert_eq!(pass, vec![4]);
+        assert_eq!(complicated, vec![0, 1, 2]);
+
+        // boxes are laid flat when that is what fits
+        let bar = vec![Shape {
+            index: 0,
+            filled: vec![vec![true]; 5],
+        }];
+        assert!(shelf_packing(&region((5, 3), &[3]), &bar).is_some());
+        assert!(shelf_packing(&region((4, 3), &[3]), &bar).is_none());
+
+        let placements = shelf_packing(&regions[4], &shapes).unwrap();
+        assert_eq!(placements.len(), 6);
+        assert_eq!(placements[5].shape, 5);
+        assert!(placements[5].cells.contains(&(5, 8)));
+    }
+
+    #[test]
+    fn test_canonical() {
+        // an S and a Z tetromino are mirror images, so the same shape
+        let s = shape(&[".##", "##."]);
+        let z = shape(&["##.", ".##", "..."]);
+        let t = shape(&["###", ".#."]);
+        assert_eq!(s, z);
+        assert_ne!(s, t);
+
+        let distinct: HashSet<Shape> = [s, z, t.rotated(), t].into_iter().collect();
+        assert_eq!(distinct.len(), 2);
+    }
 }
@@ -0,0 +1,541 @@
+// Exact packing of presents into a region. Cells are visited in reading order and the first empty
+// one is either covered by a present whose own first cell lands on it, or left empty if the region
+// has room to spare. Every packing is then found exactly once, whatever order its presents went in.
+//
+// Two things prune the search. Each free cell keeps a count of the candidate placements that could
+// still cover it, and once more cells have run out than may be left empty the branch is dead.
+// Everything before the current cell is settled and presents only reach a few rows ahead, so the
+// cells in that window and the presents left are the whole state, and states that have already
+// failed with as much slack are skipped.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::{Region, Shape, find_shape};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Placement {
+    pub shape: usize,
+    // (row, column) of every cell the present covers
+    pub cells: Vec<(usize, usize)>,
+}
+
+// How long a single region may be searched before giving up on it
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Budget {
+    pub nodes: Option<u64>,
+    pub time: Option<Duration>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Fits(Vec<Placement>),
+    DoesNotFit,
+    // the budget ran out first
+    Unknown,
+}
+
+// One orientation of a present at one spot, as sorted indices into the grid in reading order
+struct Candidate {
+    shape: usize,
+    cells: Vec<usize>,
+}
+
+struct Packer {
+    candidates: Vec<Candidate>,
+    // the candidates whose first cell is each cell
+    starting_at: Vec<Vec<usize>>,
+    // the candidates that cover each cell
+    covering: Vec<Vec<usize>>,
+    // the candidates for each shape
+    of_shape: Vec<Vec<usize>>,
+    remaining: Vec<u32>,
+    presents_left: u32,
+    // covered by a present or passed over and left empty
+    occupied: Vec<bool>,
+    // how many of each candidate's cells are occupied
+    blocked: Vec<usize>,
+    // how many unblocked candidates of shapes still to place cover each cell
+    coverable: Vec<usize>,
+    // free cells with nothing left to cover them
+    dead: usize,
+    // how many more cells may be left empty, which is the free cells less the area still to place
+    slack: usize,
+    chosen: Vec<usize>,
+    // cells from the current one onwards that a present placed there could reach
+    window: usize,
+    // the most slack each state has failed with, since any less would fail too
+    failed: HashMap<(usize, Vec<u64>, Vec<u32>), usize>,
+    nodes: u64,
+    max_nodes: Option<u64>,
+    deadline: Option<Instant>,
+    // set once the budget has run out, after which every branch fails without being remembered
+    out_of_budget: bool,
+}
+
+impl Packer {
+    fn is_open(&self, candidate: usize) -> bool {
+        self.blocked[candidate] == 0 && self.remaining[self.candidates[candidate].shape] > 0
+    }
+
+    // A candidate opening up or closing changes how coverable each of its cells is
+    fn open_candidate(&mut self, candidate: usize, open: bool) {
+        for i in 0..self.candidates[candidate].cells.len() {
+            let cell = self.candidates[candidate].cells[i];
+            let is_free = !self.occupied[cell];
+            if open {
+                if is_free && self.coverable[cell] == 0 {
+                    self.dead -= 1;
+                }
+                self.coverable[cell] += 1;
+            } else {
+                self.coverable[cell] -= 1;
+                if is_free && self.coverable[cell] == 0 {
+                    self.dead += 1;
+                }
+            }
+        }
+    }
+
+    fn occupy(&mut self, cell: usize) {
+        if self.coverable[cell] == 0 {
+            self.dead -= 1;
+        }
+        self.occupied[cell] = true;
+        for i in 0..self.covering[cell].len() {
+            let candidate = self.covering[cell][i];
+            let was_open = self.is_open(candidate);
+            self.blocked[candidate] += 1;
+            if was_open {
+                self.open_candidate(candidate, false);
+            }
+        }
+    }
+
+    fn release(&mut self, cell: usize) {
+        for i in 0..self.covering[cell].len() {
+            let candidate = self.covering[cell][i];
+            self.blocked[candidate] -= 1;
+            if self.is_open(candidate) {
+                self.open_candidate(candidate, true);
+            }
+        }
+        self.occupied[cell] = false;
+        if self.coverable[cell] == 0 {
+            self.dead += 1;
+        }
+    }
+
+    // Moves the remaining count of a shape, opening or closing its candidates when it hits zero
+    fn set_remaining(&mut self, shape: usize, remaining: u32) {
+        let was_zero = self.remaining[shape] == 0;
+        if remaining == 0 && !was_zero {
+            for i in 0..self.of_shape[shape].len() {
+                let candidate = self.of_shape[shape][i];
+                if self.is_open(candidate) {
+                    self.open_candidate(candidate, false);
+                }
+            }
+        }
+        self.remaining[shape] = remaining;
+        if remaining > 0 && was_zero {
+            for i in 0..self.of_shape[shape].len() {
+                let candidate = self.of_shape[shape][i];
+                if self.is_open(candidate) {
+                    self.open_candidate(candidate, true);
+                }
+            }
+        }
+    }
+
+    fn place(&mut self, candidate: usize) {
+        for i in 0..self.candidates[candidate].cells.len() {
+            self.occupy(self.candidates[candidate].cells[i]);
+        }
+        let shape = self.candidates[candidate].shape;
+        self.set_remaining(shape, self.remaining[shape] - 1);
+        self.presents_left -= 1;
+        self.chosen.push(candidate);
+    }
+
+    fn unplace(&mut self, candidate: usize) {
+        self.chosen.pop();
+        self.presents_left += 1;
+        let shape = self.candidates[candidate].shape;
+        self.set_remaining(shape, self.remaining[shape] + 1);
+        for i in (0..self.candidates[candidate].cells.len()).rev() {
+            self.release(self.candidates[candidate].cells[i]);
+        }
+    }
+
+    fn state(&self, cell: usize) -> (usize, Vec<u64>, Vec<u32>) {
+        let end = (cell + self.window).min(self.occupied.len());
+        let mut bits = vec![0u64; (end - cell).div_ceil(64)];
+        for (i, &occupied) in self.occupied[cell..end].iter().enumerate() {
+            if occupied {
+                bits[i / 64] |= 1 << (i % 64);
+            }
+        }
+        (cell, bits, self.remaining.clone())
+    }
+
+    fn spend_node(&mut self) -> bool {
+        self.nodes += 1;
+        // checking the clock on every node would cost more than the nodes themselves
+        let late = self.nodes.is_multiple_of(1024)
+            && self
+                .deadline
+                .is_some_and(|deadline| Instant::now() > deadline);
+        if late
+            || self
+                .max_nodes
+                .is_some_and(|max_nodes| self.nodes > max_nodes)
+        {
+            self.out_of_budget = true;
+        }
+        !self.out_of_budget
+    }
+
+    fn search(&mut self, from: usize) -> bool {
+        if self.presents_left == 0 {
+            return true;
+        }
+        if !self.spend_node() {
+            return false;
+        }
+        if self.dead > self.slack {
+            return false;
+        }
+
+        let Some(cell) = (from..self.occupied.len()).find(|&cell| !self.occupied[cell]) else {
+            return false;
+        };
+        let state = self.state(cell);
+        if self
+            .failed
+            .get(&state)
+            .is_some_and(|&slack| self.slack <= slack)
+        {
+            return false;
+        }
+
+        for i in 0..self.starting_at[cell].len() {
+            let candidate = self.starting_at[cell][i];
+            if !self.is_open(candidate) {
+                continue;
+            }
+
+            self.place(candidate);
+            if self.search(cell + 1) {
+                return true;
+            }
+            self.unplace(candidate);
+        }
+
+        // leave the cell empty, which closes every candidate over it
+        if self.slack > 0 {
+            self.slack -= 1;
+            self.occupy(cell);
+            if self.search(cell + 1) {
+                return true;
+            }
+            self.release(cell);
+            self.slack += 1;
+        }
+
+        if !self.out_of_budget {
+            self.failed.insert(state, self.slack);
+        }
+        false
+    }
+}
+
+pub fn present_area(region: &Region, shapes: &[Shape]) -> usize {
+    region
+        .shapes
+        .iter()
+        .enumerate()
+        .map(|(index, &count)| count as usize * find_shape(shapes, index).cell_count())
+        .sum()
+}
+
+// Every orientation of every shape the region asks for at every spot where it lies inside
+pub fn all_placements(region: &Region, shapes: &[Shape]) -> Vec<Placement> {
+    let (width, height) = region.area;
+    let mut placements = Vec::new();
+    for (index, &count) in region.shapes.iter().enumerate() {
+        let shape = find_shape(shapes, index);
+        assert!(shape.cell_count() > 0, "Shape {} has no cells", index);
+        if count == 0 {
+            continue;
+        }
+
+        for orientation in shape.orientations() {
+            let cells = orientation.cells();
+            let rows = orientation.filled.len();
+            let cols = orientation.filled[0].len();
+            for top in 0..(height + 1).saturating_sub(rows) {
+                for left in 0..(width + 1).saturating_sub(cols) {
+                    placements.push(Placement {
+                        shape: index,
+                        cells: cells
+                            .iter()
+                            .map(|&(row, col)| (top + row, left + col))
+                            .collect(),
+                    });
+                }
+            }
+        }
+    }
+    placements
+}
+
+// Whether the placements put exactly the presents the region asks for inside it, each one a real
+// orientation of its shape, without any two overlapping
+pub fn verify(region: &Region, shapes: &[Shape], placements: &[Placement]) -> bool {
+    let (width, height) = region.area;
+    let mut covered = vec![false; width * height];
+    let mut counts = vec![0; region.shapes.len()];
+
+    for placement in placements {
+        if placement.shape >= counts.len() {
+            return false;
+        }
+        counts[placement.shape] += 1;
+
+        for &(row, col) in &placement.cells {
+            if row >= height || col >= width || covered[row * width + col] {
+                return false;
+            }
+            covered[row * width + col] = true;
+        }
+
+        let mut filled = vec![vec![false; width]; height];
+        for &(row, col) in &placement.cells {
+            filled[row][col] = true;
+        }
+        let drawn = Shape {
+            index: placement.shape,
+            filled,
+        };
+        if drawn.cell_count() != placement.cells.len()
+            || &drawn != find_shape(shapes, placement.shape)
+        {
+            return false;
+        }
+    }
+
+    counts == region.shapes
+}
+
+// Places every present the region asks for without overlaps, finds that it can't be done, or runs
+// out of budget
+pub fn pack(region: &Region, shapes: &[Shape], budget: &Budget) -> Status {
+    // Scanning across the narrower side keeps the window of unsettled cells small. Every shape is
+    // tried in every orientation, so packing the transposed region is just as good.
+    let transposed = region.area.0 > region.area.1;
+    let (width, height) = if transposed {
+        (region.area.1, region.area.0)
+    } else {
+        region.area
+    };
+    let num_cells = width * height;
+
+    let present_area = present_area(region, shapes);
+    if present_area > num_cells {
+        return Status::DoesNotFit;
+    }
+
+    let placements = all_placements(region, shapes);
+    let candidates: Vec<Candidate> = placements
+        .iter()
+        .map(|placement| {
+            let mut cells: Vec<usize> = placement
+                .cells
+                .iter()
+                .map(|&(row, col)| {
+                    if transposed {
+                        col * width + row
+                    } else {
+                        row * width + col
+                    }
+                })
+                .collect();
+            cells.sort();
+            Candidate {
+                shape: placement.shape,
+                cells,
+            }
+        })
+        .collect();
+    let tallest = candidates
+        .iter()
+        .map(|candidate| candidate.cells.last().unwrap() / width - candidate.cells[0] / width + 1)
+        .max()
+        .unwrap_or(1);
+
+    let mut starting_at = vec![Vec::new(); num_cells];
+    let mut covering = vec![Vec::new(); num_cells];
+    let mut of_shape = vec![Vec::new(); region.shapes.len()];
+    for (i, candidate) in candidates.iter().enumerate() {
+        starting_at[candidate.cells[0]].push(i);
+        of_shape[candidate.shape].push(i);
+        for &cell in &candidate.cells {
+            covering[cell].push(i);
+        }
+    }
+    let coverable: Vec<usize> = covering.iter().map(Vec::len).collect();
+
+    let mut packer = Packer {
+        blocked: vec![0; candidates.len()],
+        candidates,
+        starting_at,
+        covering,
+        of_shape,
+        remaining: region.shapes.clone(),
+        presents_left: region.shapes.iter().sum(),
+        occupied: vec![false; num_cells],
+        dead: coverable.iter().filter(|&&count| count == 0).count(),
+        coverable,
+        slack: num_cells - present_area,
+        chosen: Vec::new(),
+        window: tallest * width,
+        failed: HashMap::new(),
+        nodes: 0,
+        max_nodes: budget.nodes,
+        deadline: budget.time.map(|time| Instant::now() + time),
+        out_of_budget: false,
+    };
+
+    if packer.search(0) {
+        Status::Fits(
+            packer
+                .chosen
+                .iter()
+                .map(|&candidate| placements[candidate].clone())
+                .collect(),
+        )
+    } else if packer.out_of_budget {
+        Status::Unknown
+    } else {
+        Status::DoesNotFit
+    }
+}
+
+// Packs the regions on a pool of threads, each taking the next unclaimed region until none are
+// left. progress is called on this thread with the number finished so far, every so often and once
+// at the end.
+pub fn pack_all(
+    regions: &[&Region],
+    shapes: &[Shape],
+    budget: &Budget,
+    threads: usize,
+    mut progress: impl FnMut(usize),
+) -> Vec<Status> {
+    let next = AtomicUsize::new(0);
+    let finished = AtomicUsize::new(0);
+    let statuses = Mutex::new(vec![Status::Unknown; regions.len()]);
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, regions.len().max(1)) {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= regions.len() {
+                        break;
+                    }
+                    let status = pack(regions[i], shapes, budget);
+                    statuses.lock().unwrap()[i] = status;
+                    finished.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        }
+
+        while finished.load(Ordering::Relaxed) < regions.len() {
+            progress(finished.load(Ordering::Relaxed));
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    });
+
+    progress(regions.len());
+    statuses.into_inner().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_input;
+    use crate::tests::EXAMPLE;
+
+    #[test]
+    fn test_example() {
+        let (shapes, regions) = parse_input(EXAMPLE).unwrap();
+
+        let unlimited = Budget::default();
+        for region in &regions[..2] {
+            let Status::Fits(placements) = pack(region, &shapes, &unlimited) else {
+                panic!("{:?} should fit", region);
+            };
+            assert!(verify(region, &shapes, &placements));
+        }
+        assert_eq!(pack(&regions[2], &shapes, &unlimited), Status::DoesNotFit);
+    }
+
+    #[test]
+    fn test_budget() {
+        let (shapes, regions) = parse_input(EXAMPLE).unwrap();
+        let budget = Budget {
+            nodes: Some(100),
+            time: None,
+        };
+        assert_eq!(pack(&regions[2], &shapes, &budget), Status::Unknown);
+
+        let budget = Budget {
+            nodes: None,
+            time: Some(Duration::ZERO),
+        };
+        assert_eq!(pack(&regions[2], &shapes, &budget), Status::Unknown);
+    }
+
+    #[test]
+    fn test_pack_all() {
+        let (shapes, regions) = parse_input(EXAMPLE).unwrap();
+        let expected: Vec<Status> = regions
+            .iter()
+            .map(|region| pack(region, &shapes, &Budget::default()))
+            .collect();
+        let regions: Vec<&Region> = regions.iter().cycle().take(6).collect();
+
+        let mut reported = Vec::new();
+        let statuses = pack_all(&regions, &shapes, &Budget::default(), 4, |done| {
+            reported.push(done)
+        });
+        assert_eq!(reported.last(), Some(&6));
+        assert_eq!(statuses[..3], expected);
+        assert_eq!(statuses[3..], expected);
+    }
+
+    #[test]
+    fn test_verify() {
+        let (shapes, regions) = parse_input(EXAMPLE).unwrap();
+        let region = &regions[0];
+        let Status::Fits(placements) = pack(region, &shapes, &Budget::default()) else {
+            panic!("{:?} should fit", region);
+        };
+
+        // a missing present, an overlap, a shape that isn't its own, and a present outside
+        assert!(!verify(region, &shapes, &placements[..1]));
+        let overlapping = vec![placements[0].clone(), placements[0].clone()];
+        assert!(!verify(region, &shapes, &overlapping));
+        let mut wrong_shape = placements.clone();
+        wrong_shape[1].cells[0] = (3, 3);
+        assert!(!verify(region, &shapes, &wrong_shape));
+        let mut outside = placements.clone();
+        outside[1].cells[0].1 += 4;
+        assert!(!verify(region, &shapes, &outside));
+
+        assert_eq!(all_placements(region, &shapes).len(), 16);
+    }
+}
@@ -0,0 +1,103 @@
+// Pictures of packed regions: a letter per present as text, or a colour per present as a PPM image
+
+use crate::Region;
+use crate::packing::Placement;
+
+const LETTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const UNUSED: char = '.';
+
+// Which present covers each cell, row by row
+fn owners(region: &Region, placements: &[Placement]) -> Vec<Vec<Option<usize>>> {
+    let (width, height) = region.area;
+    let mut grid = vec![vec![None; width]; height];
+    for (present, placement) in placements.iter().enumerate() {
+        for &(row, col) in &placement.cells {
+            grid[row][col] = Some(present);
+        }
+    }
+    grid
+}
+
+// One line per row. Presents take the letters in turn, so only packings of more than 62 presents
+// reuse a letter.
+pub fn to_ascii(region: &Region, placements: &[Placement]) -> String {
+    let mut text = String::new();
+    for row in owners(region, placements) {
+        text.extend(row.iter().map(|owner| match owner {
+            Some(present) => LETTERS[present % LETTERS.len()] as char,
+            None => UNUSED,
+        }));
+        text.push('\n');
+    }
+    text
+}
+
+// Evenly spread hues so that neighbouring presents stand apart
+fn colour(present: usize) -> [u8; 3] {
+    let hue = (present as f64 * 0.618_033_988_75).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as usize {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    [r, g, b].map(|channel| (64.0 + channel * 191.0) as u8)
+}
+
+// Binary PPM with each cell drawn as a square of scale pixels. Unused cells are dark grey and every
+// cell keeps a one pixel black border so the grid stays visible.
+pub fn to_ppm(region: &Region, placements: &[Placement], scale: usize) -> Vec<u8> {
+    let (width, height) = region.area;
+    let grid = owners(region, placements);
+
+    let mut image = format!("P6\n{} {}\n255\n", width * scale, height * scale).into_bytes();
+    for pixel_row in 0..height * scale {
+        for pixel_col in 0..width * scale {
+            let on_border = pixel_row % scale == 0 || pixel_col % scale == 0;
+            let rgb = match grid[pixel_row / scale][pixel_col / scale] {
+                _ if on_border && scale > 2 => [0, 0, 0],
+                Some(present) => colour(present),
+                None => [48, 48, 48],
+            };
+            image.extend(rgb);
+        }
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii() {
+        let region = Region {
+            area: (4, 3),
+            shapes: vec![2],
+        };
+        let placements = [
+            Placement {
+                shape: 0,
+                cells: vec![(0, 0), (0, 1), (1, 0)],
+            },
+            Placement {
+                shape: 0,
+                cells: vec![(1, 3), (2, 2), (2, 3)],
+            },
+        ];
+        assert_eq!(to_ascii(&region, &placements), "AA..\nA..B\n..BB\n");
+
+        let image = to_ppm(&region, &placements, 4);
+        let header = b"P6\n16 12\n255\n";
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(image.len(), header.len() + 16 * 12 * 3);
+        // inside the first cell, then inside the unused one next to it
+        let pixel = |x: usize, y: usize| &image[header.len() + (y * 16 + x) * 3..][..3];
+        assert_eq!(pixel(1, 1), colour(0));
+        assert_eq!(pixel(9, 1), [48, 48, 48]);
+        assert_ne!(colour(0), colour(1));
+    }
+}